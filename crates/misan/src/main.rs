@@ -7,11 +7,12 @@ use std::{
 use clap::{Args, Parser, Subcommand};
 use colored::*;
 use env_logger::Builder;
+use futures_util::TryStreamExt;
 use log::{debug, error, info, LevelFilter};
 
 use misanthropy::{
-    Anthropic, Content, MessagesRequest, ANTHROPIC_API_KEY_ENV, ANTHROPIC_API_VERSION,
-    DEFAULT_MAX_TOKENS, DEFAULT_MODEL,
+    Anthropic, Content, ListParams, MessagesRequest, ModelInfo, ANTHROPIC_API_KEY_ENV,
+    ANTHROPIC_API_VERSION, DEFAULT_MAX_TOKENS, DEFAULT_MODEL,
 };
 
 fn setup_logger(verbose: u8, quiet: bool) {
@@ -68,6 +69,8 @@ enum Commands {
     Message(MessageArgs),
     /// Stream a message from the API
     Stream(MessageArgs),
+    /// List the models available through the API
+    Models,
     /// Display information about the tool and API
     Info,
 }
@@ -368,6 +371,21 @@ fn build_request(
     Ok(request)
 }
 
+async fn handle_models(anthropic: &Anthropic, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let models: Vec<ModelInfo> = anthropic
+        .list_models(ListParams::default())
+        .try_collect()
+        .await?;
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&models)?);
+    } else {
+        for model in models {
+            println!("{}\t{}", model.id, model.display_name);
+        }
+    }
+    Ok(())
+}
+
 fn handle_info(cli: &Cli) {
     println!("Misan:");
     println!("\tVersion: {}", env!("CARGO_PKG_VERSION"));
@@ -401,6 +419,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::Stream(args) => {
             handle_stream(&anthropic, args, &cli).await?;
         }
+        Commands::Models => {
+            handle_models(&anthropic, &cli).await?;
+        }
         Commands::Info => {
             handle_info(&cli);
         }
@@ -1,4 +1,7 @@
-use misanthropy::{Anthropic, Content, MessagesRequest, Tool, ToolChoice, DEFAULT_MODEL};
+use misanthropy::{
+    Anthropic, Content, MessagesRequest, ToolChoice, ToolError, ToolHandler, ToolRegistry,
+    ToolResultContent, DEFAULT_MODEL,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -9,6 +12,21 @@ struct GetStockPrice {
     ticker: String,
 }
 
+struct StockPrice;
+
+impl ToolHandler for StockPrice {
+    type Input = GetStockPrice;
+
+    fn name(&self) -> &str {
+        "stockprice"
+    }
+
+    async fn call(&self, input: GetStockPrice) -> Result<ToolResultContent, ToolError> {
+        println!("Looking up {:#?}", input.ticker);
+        Ok("The stock price is $150.00".into())
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let anthropic = Anthropic::from_env()?;
@@ -26,23 +44,19 @@ async fn make_request(
     anthropic: &Anthropic,
     with_tool_choice: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let get_stock_price_tool = Tool::custom::<GetStockPrice>("stockprice")?;
-    let get_stock_price_tool_name = match &get_stock_price_tool {
-        Tool::Custom { name, .. } => name.clone(),
-        Tool::TextEditor { name, .. } => name.clone(),
-    };
+    let tools = ToolRegistry::new().with_handler(StockPrice);
     let request = MessagesRequest::default()
         .with_model(DEFAULT_MODEL.to_string())
         .with_max_tokens(1000)
-        .with_tool(get_stock_price_tool)
+        .with_tools(tools.tools()?)
         .with_system(vec![Content::text(
             "You are a helpful assistant that can look up stock prices.",
         )]);
 
     let mut request = if with_tool_choice {
-        request.with_tool_choice(ToolChoice::Tool {
-            name: get_stock_price_tool_name,
-        })
+        request
+            .with_tool_choice(ToolChoice::tool(StockPrice.name()))
+            .with_disable_parallel_tool_use(true)
     } else {
         request
     };
@@ -55,29 +69,11 @@ async fn make_request(
     println!("Claude's response:");
     println!("{}", response.format_content());
 
-    // Check for tool use in the response
+    // Run the tools Claude asked for and send back the results
     println!("------------------------------------");
-    for content in response.content {
-        request.add_assistant(content.clone());
-        if let Content::ToolUse(tool_use) = content {
-            println!("Tool Use Detected:");
-            println!("Tool Name: {}", tool_use.name);
-            println!("Tool ID: {}", tool_use.id);
-
-            match serde_json::from_value::<GetStockPrice>(tool_use.input.clone()) {
-                Ok(get_stock_price) => {
-                    println!("{:#?}", get_stock_price.ticker);
-                    request.add_user(Content::tool_result(
-                        &tool_use,
-                        "The stock price is $150.00",
-                    ));
-                }
-                Err(e) => {
-                    eprintln!("Failed to parse tool input: {e}");
-                    return Ok(());
-                }
-            }
-        }
+    request.merge_response(&response);
+    for result in tools.run(&response).await {
+        request.add_user(result);
     }
 
     // Continue the conversation and get the next response
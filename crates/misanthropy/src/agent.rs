@@ -0,0 +1,673 @@
+//! A tool use loop that keeps sending requests until the model is done.
+use std::{
+    collections::VecDeque,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures_util::{stream::FuturesUnordered, StreamExt};
+
+use crate::{
+    Anthropic, Content, ContentBlockDelta, MessagesRequest, MessagesResponse, Result, StopReason,
+    StreamEvent, StreamedResponse, ToolRegistry, ToolResult, ToolUse, Usage,
+};
+
+/// Runs a conversation with tools: sends the request, runs the tools the model asks for, adds
+/// the results to the conversation and repeats until the model stops using tools.
+///
+/// Limits on turns, tokens and time are checked between turns, so the first request is always
+/// sent and a turn in progress is never interrupted. Tool uses within a turn run concurrently.
+///
+/// ```no_run
+/// # use misanthropy::{Agent, Anthropic, Content, MessagesRequest, ToolRegistry};
+/// # async fn example(client: Anthropic, tools: ToolRegistry) -> misanthropy::Result<()> {
+/// let mut request = MessagesRequest::default();
+/// request.add_user(Content::text("What is Apple's stock price today?"));
+/// let run = Agent::new(client, tools).with_max_turns(5).run(request).await?;
+/// println!("{}", run.response.format_content());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Agent {
+    client: Anthropic,
+    tools: ToolRegistry,
+    max_turns: u32,
+    max_tokens: Option<u32>,
+    max_duration: Option<Duration>,
+}
+
+/// Why an agent run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentStop {
+    /// The model finished without asking for more tools. The final response's stop reason says
+    /// why, e.g. `StopReason::EndTurn` or `StopReason::MaxTokens`.
+    Finished,
+    /// The maximum number of turns was reached.
+    MaxTurns,
+    /// The total input and output tokens reached the limit.
+    MaxTokens,
+    /// The run took longer than the time limit.
+    Timeout,
+    /// The run was cancelled with `AgentStream::cancel`.
+    Cancelled,
+}
+
+/// The outcome of an agent run.
+#[derive(Debug)]
+pub struct AgentRun {
+    /// The last response from the model.
+    pub response: MessagesResponse,
+    /// The request with the full conversation, including every response and tool result. If
+    /// the run stopped on a limit, pass it to `Agent::run` again to continue.
+    pub transcript: MessagesRequest,
+    /// Token usage summed over every turn.
+    pub usage: Usage,
+    /// The number of requests sent.
+    pub turns: u32,
+    /// Why the run ended.
+    pub stop: AgentStop,
+}
+
+impl Agent {
+    /// Creates an agent that runs tools from `tools`, allowing up to 10 turns by default.
+    pub fn new(client: Anthropic, tools: ToolRegistry) -> Self {
+        Self {
+            client,
+            tools,
+            max_turns: 10,
+            max_tokens: None,
+            max_duration: None,
+        }
+    }
+
+    /// Sets the maximum number of requests to send.
+    pub fn with_max_turns(mut self, max_turns: u32) -> Self {
+        self.max_turns = max_turns;
+        self
+    }
+
+    /// Sets the maximum number of input and output tokens to use over all turns.
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Sets the maximum time to spend on the run, including running tools.
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// Adds the definitions of any registered tools the request doesn't already have.
+    fn prepare(&self, mut request: MessagesRequest) -> Result<MessagesRequest> {
+        for tool in self.tools.tools()? {
+            if !request.tools.iter().any(|t| t.name() == tool.name()) {
+                request.tools.push(tool);
+            }
+        }
+        Ok(request)
+    }
+
+    /// The limit reached after `turns` turns using `usage` tokens, if any.
+    fn limit(&self, turns: u32, usage: &Usage, started: Instant) -> Option<AgentStop> {
+        let tokens = usage.input_tokens.unwrap_or(0) + usage.output_tokens.unwrap_or(0);
+        if turns >= self.max_turns {
+            Some(AgentStop::MaxTurns)
+        } else if self.max_tokens.is_some_and(|max| tokens >= max) {
+            Some(AgentStop::MaxTokens)
+        } else if self
+            .max_duration
+            .is_some_and(|max| started.elapsed() >= max)
+        {
+            Some(AgentStop::Timeout)
+        } else {
+            None
+        }
+    }
+
+    /// Runs the conversation until the model stops using tools or a limit is reached.
+    pub async fn run(&self, request: MessagesRequest) -> Result<AgentRun> {
+        let started = Instant::now();
+        let mut request = self.prepare(request)?;
+        let mut usage = Usage::default();
+        let mut turns = 0;
+        loop {
+            let response = self.client.messages(&request).await?;
+            turns += 1;
+            usage = usage.merge(&response.usage);
+            request.merge_response(&response);
+
+            match response.stop_reason {
+                Some(StopReason::ToolUse) => {
+                    for result in self.tools.run(&response).await {
+                        request.add_user(result);
+                    }
+                }
+                // The model paused a long-running turn, and continues when the response is
+                // sent back.
+                Some(StopReason::PauseTurn) => {}
+                _ => {
+                    return Ok(AgentRun {
+                        response,
+                        transcript: request,
+                        usage,
+                        turns,
+                        stop: AgentStop::Finished,
+                    })
+                }
+            }
+
+            if let Some(stop) = self.limit(turns, &usage, started) {
+                return Ok(AgentRun {
+                    response,
+                    transcript: request,
+                    usage,
+                    turns,
+                    stop,
+                });
+            }
+        }
+    }
+}
+
+/// A progress update from a streaming agent run.
+#[derive(Debug)]
+pub enum AgentEvent {
+    /// A request is being sent to the model. Turns are numbered from 1.
+    TurnStarted { turn: u32 },
+    /// Text generated by the model.
+    TextDelta { index: usize, text: String },
+    /// Thinking generated by the model.
+    ThinkingDelta { index: usize, thinking: String },
+    /// The model asked for a tool. Sent once the tool input has been fully streamed.
+    ToolCallStarted(ToolUse),
+    /// A tool finished running. Tools run concurrently, so results arrive in completion order.
+    ToolCallFinished(ToolResult),
+    /// The model finished responding.
+    TurnFinished {
+        turn: u32,
+        stop_reason: Option<StopReason>,
+    },
+    /// Token usage over the whole run so far.
+    Usage(Usage),
+    /// The run is over. This is always the last event.
+    Finished(Box<AgentRun>),
+}
+
+type ToolCall = Pin<Box<dyn Future<Output = (usize, ToolResult)> + Send>>;
+
+enum StreamState {
+    StartTurn,
+    Streaming(Box<StreamedResponse>),
+    RunningTools {
+        calls: FuturesUnordered<ToolCall>,
+        results: Vec<Option<ToolResult>>,
+    },
+    Done,
+}
+
+/// A streaming agent run, created by `Agent::run_stream`. Call `next` to drive the run and
+/// receive progress events.
+pub struct AgentStream {
+    agent: Agent,
+    request: MessagesRequest,
+    response: MessagesResponse,
+    usage: Usage,
+    turns: u32,
+    started: Instant,
+    cancelled: Arc<AtomicBool>,
+    events: VecDeque<AgentEvent>,
+    state: StreamState,
+}
+
+/// A handle for cancelling an `AgentStream` from another task.
+#[derive(Debug, Clone)]
+pub struct AgentCancel(Arc<AtomicBool>);
+
+impl AgentCancel {
+    /// Stops the run before its next turn.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Agent {
+    /// Runs the conversation like `run`, streaming each response and reporting progress as
+    /// `AgentEvent`s.
+    pub fn run_stream(&self, request: MessagesRequest) -> Result<AgentStream> {
+        let request = self.prepare(request)?.with_stream(true);
+        Ok(AgentStream {
+            agent: self.clone(),
+            request,
+            response: MessagesResponse::default(),
+            usage: Usage::default(),
+            turns: 0,
+            started: Instant::now(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            events: VecDeque::new(),
+            state: StreamState::StartTurn,
+        })
+    }
+}
+
+impl AgentStream {
+    /// Stops the run before its next turn. The current turn and its tools finish first.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns a handle that cancels this run, for use from another task.
+    pub fn cancel_handle(&self) -> AgentCancel {
+        AgentCancel(self.cancelled.clone())
+    }
+
+    /// Retrieves the next event, or `None` once the run is over. The last event is always
+    /// `AgentEvent::Finished`, unless an error is returned first.
+    pub async fn next(&mut self) -> Option<Result<AgentEvent>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+            match &mut self.state {
+                StreamState::Done => return None,
+                StreamState::StartTurn => {
+                    if self.cancelled.load(Ordering::Relaxed) {
+                        self.finish(AgentStop::Cancelled);
+                        continue;
+                    }
+                    match self.agent.client.messages_stream(&self.request) {
+                        Ok(stream) => self.state = StreamState::Streaming(Box::new(stream)),
+                        Err(e) => {
+                            self.state = StreamState::Done;
+                            return Some(Err(e));
+                        }
+                    }
+                    self.turns += 1;
+                    return Some(Ok(AgentEvent::TurnStarted { turn: self.turns }));
+                }
+                StreamState::Streaming(stream) => match stream.next().await {
+                    // The turn failed partway through, so its partial response is discarded.
+                    Some(Ok(StreamEvent::Error { error })) => {
+                        self.state = StreamState::Done;
+                        return Some(Err(error.into()));
+                    }
+                    Some(Ok(event)) => {
+                        if let Some(event) = self.stream_event(event) {
+                            return Some(Ok(event));
+                        }
+                    }
+                    Some(Err(e)) => {
+                        self.state = StreamState::Done;
+                        return Some(Err(e));
+                    }
+                    None => self.end_turn(),
+                },
+                StreamState::RunningTools { calls, results } => match calls.next().await {
+                    Some((i, result)) => {
+                        results[i] = Some(result.clone());
+                        return Some(Ok(AgentEvent::ToolCallFinished(result)));
+                    }
+                    None => {
+                        for result in mem::take(results).into_iter().flatten() {
+                            self.request.add_user(Content::ToolResult(result));
+                        }
+                        self.next_turn();
+                    }
+                },
+            }
+        }
+    }
+
+    /// Converts an event from the response stream into a progress event, if it is of interest.
+    fn stream_event(&self, event: StreamEvent) -> Option<AgentEvent> {
+        let StreamState::Streaming(stream) = &self.state else {
+            return None;
+        };
+        match event {
+            StreamEvent::ContentBlockDelta {
+                index,
+                delta: ContentBlockDelta::TextDelta { text },
+            } => Some(AgentEvent::TextDelta { index, text }),
+            StreamEvent::ContentBlockDelta {
+                index,
+                delta: ContentBlockDelta::ThinkingDelta { thinking },
+            } => Some(AgentEvent::ThinkingDelta { index, thinking }),
+            // The streamed response has assembled the tool input by the time the block stops.
+            StreamEvent::ContentBlockStop { index } => match stream.response.content.get(index) {
+                Some(Content::ToolUse(tool_use)) => {
+                    Some(AgentEvent::ToolCallStarted(tool_use.clone()))
+                }
+                _ => None,
+            },
+            StreamEvent::MessageDelta { .. } => {
+                Some(AgentEvent::Usage(self.usage.merge(&stream.response.usage)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Adds the streamed response to the conversation and starts any tools it asks for.
+    fn end_turn(&mut self) {
+        let StreamState::Streaming(stream) = mem::replace(&mut self.state, StreamState::Done)
+        else {
+            return;
+        };
+        let response = stream.response;
+        self.usage = self.usage.merge(&response.usage);
+        self.request.merge_response(&response);
+        self.events.push_back(AgentEvent::TurnFinished {
+            turn: self.turns,
+            stop_reason: response.stop_reason.clone(),
+        });
+
+        match response.stop_reason {
+            Some(StopReason::ToolUse) => {
+                let calls = FuturesUnordered::new();
+                for (i, tool_use) in response.tool_uses().enumerate() {
+                    let tools = self.agent.tools.clone();
+                    let tool_use = tool_use.clone();
+                    calls.push(
+                        Box::pin(async move { (i, tools.tool_result(&tool_use).await) })
+                            as ToolCall,
+                    );
+                }
+                let results = vec![None; calls.len()];
+                self.response = response;
+                self.state = StreamState::RunningTools { calls, results };
+            }
+            Some(StopReason::PauseTurn) => {
+                self.response = response;
+                self.next_turn();
+            }
+            _ => {
+                self.response = response;
+                self.finish(AgentStop::Finished);
+            }
+        }
+    }
+
+    /// Starts the next turn, unless a limit has been reached.
+    fn next_turn(&mut self) {
+        match self.agent.limit(self.turns, &self.usage, self.started) {
+            Some(stop) => self.finish(stop),
+            None => self.state = StreamState::StartTurn,
+        }
+    }
+
+    fn finish(&mut self, stop: AgentStop) {
+        self.state = StreamState::Done;
+        self.events
+            .push_back(AgentEvent::Finished(Box::new(AgentRun {
+                response: mem::take(&mut self.response),
+                transcript: mem::take(&mut self.request),
+                usage: self.usage.clone(),
+                turns: self.turns,
+                stop,
+            })));
+    }
+}
+
+impl Anthropic {
+    /// Runs a conversation with tools until the model is done, with the default `Agent` limits.
+    pub async fn run_until_done(
+        &self,
+        request: MessagesRequest,
+        tools: &ToolRegistry,
+    ) -> Result<AgentRun> {
+        Agent::new(self.clone(), tools.clone()).run(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_partial_json, method},
+        Mock, MockServer,
+    };
+
+    use super::*;
+    use crate::{
+        registry::tests::Add,
+        tests::{message_response, message_start, sse, test_client},
+        Content, Error, Role,
+    };
+
+    #[tokio::test]
+    async fn test_agent_stream() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                json!({"messages": [{}, {}, {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_01", "content": "5"}
+                ]}]}),
+            ))
+            .respond_with(sse(&[
+                message_start("msg_02", 20),
+                json!({
+                    "type": "content_block_start",
+                    "index": 0,
+                    "content_block": {"type": "text", "text": ""}
+                }),
+                json!({
+                    "type": "content_block_delta",
+                    "index": 0,
+                    "delta": {"type": "text_delta", "text": "It's 5"}
+                }),
+                json!({"type": "content_block_stop", "index": 0}),
+                json!({
+                    "type": "message_delta",
+                    "delta": {"stop_reason": "end_turn", "stop_sequence": null},
+                    "usage": {"output_tokens": 3}
+                }),
+                json!({"type": "message_stop"}),
+            ]))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(sse(&[
+                message_start("msg_01", 10),
+                json!({
+                    "type": "content_block_start",
+                    "index": 0,
+                    "content_block": {"type": "thinking", "thinking": ""}
+                }),
+                json!({
+                    "type": "content_block_delta",
+                    "index": 0,
+                    "delta": {"type": "thinking_delta", "thinking": "Use add."}
+                }),
+                json!({"type": "content_block_stop", "index": 0}),
+                json!({
+                    "type": "content_block_start",
+                    "index": 1,
+                    "content_block": {
+                        "type": "tool_use",
+                        "id": "toolu_01",
+                        "name": "add",
+                        "input": {}
+                    }
+                }),
+                json!({
+                    "type": "content_block_delta",
+                    "index": 1,
+                    "delta": {"type": "input_json_delta", "partial_json": "{\"a\": 2, "}
+                }),
+                json!({
+                    "type": "content_block_delta",
+                    "index": 1,
+                    "delta": {"type": "input_json_delta", "partial_json": "\"b\": 3}"}
+                }),
+                json!({"type": "content_block_stop", "index": 1}),
+                json!({
+                    "type": "message_delta",
+                    "delta": {"stop_reason": "tool_use", "stop_sequence": null},
+                    "usage": {"output_tokens": 5}
+                }),
+                json!({"type": "message_stop"}),
+            ]))
+            .mount(&server)
+            .await;
+
+        let agent = Agent::new(test_client(&server), ToolRegistry::new().with_handler(Add));
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::text("What is 2 + 3?"));
+
+        let mut stream = agent.run_stream(request.clone()).unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = stream.next().await {
+            events.push(match event.unwrap() {
+                AgentEvent::TurnStarted { turn } => format!("turn {turn}"),
+                AgentEvent::TextDelta { text, .. } => format!("text {text}"),
+                AgentEvent::ThinkingDelta { thinking, .. } => format!("thinking {thinking}"),
+                AgentEvent::ToolCallStarted(tool_use) => {
+                    format!("call {} {}", tool_use.name, tool_use.input)
+                }
+                AgentEvent::ToolCallFinished(result) => format!("result {}", result.content),
+                AgentEvent::TurnFinished { stop_reason, .. } => format!("end {stop_reason:?}"),
+                AgentEvent::Usage(usage) => format!("usage {:?}", usage.output_tokens),
+                AgentEvent::Finished(run) => {
+                    assert_eq!(run.response.id, "msg_02");
+                    assert_eq!(run.transcript.messages.len(), 4);
+                    format!("finished {:?}", run.stop)
+                }
+            });
+        }
+        assert_eq!(
+            events,
+            [
+                "turn 1",
+                "thinking Use add.",
+                r#"call add {"a":2,"b":3}"#,
+                "usage Some(6)",
+                "end Some(ToolUse)",
+                "result 5",
+                "turn 2",
+                "text It's 5",
+                "usage Some(10)",
+                "end Some(EndTurn)",
+                "finished Finished",
+            ]
+        );
+
+        // Cancelling stops the run before the next turn.
+        let mut stream = agent.run_stream(request).unwrap();
+        let cancel = stream.cancel_handle();
+        let mut run = None;
+        while let Some(event) = stream.next().await {
+            match event.unwrap() {
+                AgentEvent::TurnFinished { .. } => cancel.cancel(),
+                AgentEvent::Finished(finished) => run = Some(finished),
+                _ => {}
+            }
+        }
+        let run = run.unwrap();
+        assert_eq!(run.stop, AgentStop::Cancelled);
+        assert_eq!(run.turns, 1);
+        assert_eq!(run.transcript.messages.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_agent_stream_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(sse(&[
+                message_start("msg_01", 10),
+                json!({
+                    "type": "content_block_start",
+                    "index": 0,
+                    "content_block": {"type": "text", "text": ""}
+                }),
+                json!({
+                    "type": "content_block_delta",
+                    "index": 0,
+                    "delta": {"type": "text_delta", "text": "It's"}
+                }),
+                json!({
+                    "type": "error",
+                    "error": {"type": "overloaded_error", "message": "Overloaded"}
+                }),
+            ]))
+            .mount(&server)
+            .await;
+
+        let agent = Agent::new(test_client(&server), ToolRegistry::new());
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::text("What is 2 + 3?"));
+
+        // The failed turn is reported as an error rather than a finished run.
+        let mut stream = agent.run_stream(request).unwrap();
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(AgentEvent::TurnStarted { turn: 1 }))
+        ));
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(AgentEvent::TextDelta { .. }))
+        ));
+        match stream.next().await {
+            Some(Err(Error::ApiOverloaded(details))) => {
+                assert_eq!(details.status, 529);
+                assert_eq!(details.message, "Overloaded");
+            }
+            other => panic!("Expected an overloaded error, got {other:?}"),
+        }
+        assert!(stream.next().await.is_none());
+        assert_eq!(stream.request.messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_agent() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                json!({"messages": [{}, {}, {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_01", "content": "5"},
+                    {"type": "tool_result", "tool_use_id": "toolu_02", "content": "7"}
+                ]}]}),
+            ))
+            .respond_with(message_response(
+                json!([{"type": "text", "text": "5 and 7"}]),
+                "end_turn",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(message_response(
+                json!([
+                    {"type": "tool_use", "id": "toolu_01", "name": "add", "input": {"a": 2, "b": 3}},
+                    {"type": "tool_use", "id": "toolu_02", "name": "add", "input": {"a": 3, "b": 4}}
+                ]),
+                "tool_use",
+            ))
+            .mount(&server)
+            .await;
+
+        let agent = Agent::new(test_client(&server), ToolRegistry::new().with_handler(Add));
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::text("What are 2 + 3 and 3 + 4?"));
+
+        let run = agent.clone().run(request.clone()).await.unwrap();
+        assert_eq!(run.stop, AgentStop::Finished);
+        assert_eq!(run.turns, 2);
+        assert_eq!(run.response.format_content(), "5 and 7");
+        assert_eq!(run.usage.input_tokens, Some(200));
+        assert_eq!(run.transcript.tools.len(), 1);
+        assert_eq!(run.transcript.messages.len(), 4);
+        assert_eq!(run.transcript.messages[3].role, Role::Assistant);
+
+        let run = agent.with_max_turns(1).run(request).await.unwrap();
+        assert_eq!(run.stop, AgentStop::MaxTurns);
+        assert_eq!(run.turns, 1);
+        // The transcript ends with the tool results, ready to continue.
+        assert_eq!(run.transcript.messages.len(), 3);
+    }
+}
@@ -0,0 +1,349 @@
+//! The Message Batches API, for processing many message requests asynchronously at a discount.
+use std::pin::Pin;
+
+use futures_util::{stream, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    Anthropic, ApiErrorResponse, ListParams, MessagesRequest, MessagesResponse, Paginated, Result,
+};
+
+/// The processing status of a message batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    /// Requests in the batch are being processed.
+    InProgress,
+    /// Cancellation has been initiated, and in-flight requests are finishing.
+    Canceling,
+    /// All requests have finished, and results are available.
+    Ended,
+}
+
+/// Counts of requests in a batch by status.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchRequestCounts {
+    /// Requests still being processed.
+    pub processing: u32,
+    /// Requests that completed successfully.
+    pub succeeded: u32,
+    /// Requests that failed with an error.
+    pub errored: u32,
+    /// Requests that were canceled before processing.
+    pub canceled: u32,
+    /// Requests that expired before processing.
+    pub expired: u32,
+}
+
+/// A batch of message requests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageBatch {
+    /// Unique identifier for the batch.
+    pub id: String,
+    /// Always "message_batch".
+    #[serde(rename = "type")]
+    pub batch_type: String,
+    /// The processing status of the batch.
+    pub processing_status: BatchStatus,
+    /// Counts of requests in the batch by status.
+    pub request_counts: BatchRequestCounts,
+    /// RFC 3339 timestamp of when the batch was created.
+    pub created_at: String,
+    /// RFC 3339 timestamp of when the batch will expire if processing hasn't finished.
+    pub expires_at: String,
+    /// RFC 3339 timestamp of when processing ended.
+    pub ended_at: Option<String>,
+    /// RFC 3339 timestamp of when the batch was archived and its results became unavailable.
+    pub archived_at: Option<String>,
+    /// RFC 3339 timestamp of when cancellation was initiated.
+    pub cancel_initiated_at: Option<String>,
+    /// URL of the results file, available once processing has ended.
+    pub results_url: Option<String>,
+}
+
+/// Confirmation that a batch was deleted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletedBatch {
+    /// The id of the deleted batch.
+    pub id: String,
+    /// Always "message_batch_deleted".
+    #[serde(rename = "type")]
+    pub batch_type: String,
+}
+
+/// The result of a single request in a batch.
+#[derive(Debug, Deserialize)]
+pub struct BatchResult {
+    /// The id supplied for the request when the batch was created.
+    pub custom_id: String,
+    /// The outcome of the request.
+    pub result: BatchOutcome,
+}
+
+/// The outcome of a single request in a batch.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchOutcome {
+    /// The request completed successfully.
+    Succeeded {
+        /// The response to the request.
+        message: MessagesResponse,
+    },
+    /// The request failed.
+    Errored {
+        /// The error returned for the request.
+        error: ApiErrorResponse,
+    },
+    /// The batch was canceled before the request was processed.
+    Canceled,
+    /// The batch expired before the request was processed.
+    Expired,
+}
+
+/// A single request in a batch creation body.
+#[derive(Serialize)]
+struct BatchRequest<'a> {
+    custom_id: &'a str,
+    params: &'a MessagesRequest,
+}
+
+/// A stream of results from a batch.
+pub type BatchResults = Pin<Box<dyn Stream<Item = Result<BatchResult>> + Send>>;
+
+impl Anthropic {
+    /// Creates a batch from a list of `(custom_id, request)` pairs. Custom ids must be unique
+    /// within the batch, and are used to match results to requests.
+    pub async fn create_batch(
+        &self,
+        requests: &[(String, MessagesRequest)],
+    ) -> Result<MessageBatch> {
+        let mut betas = Vec::new();
+        for (_, request) in requests {
+            for beta in request.betas() {
+                if !betas.contains(&beta) {
+                    betas.push(beta);
+                }
+            }
+        }
+        let requests: Vec<BatchRequest> = requests
+            .iter()
+            .map(|(custom_id, params)| BatchRequest { custom_id, params })
+            .collect();
+        let body = serde_json::json!({ "requests": requests });
+        let builder = self.request(reqwest::Method::POST, "/v1/messages/batches", &betas)?;
+        let response = self.send(builder.json(&body)).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Retrieves a batch. Poll this until `processing_status` is `Ended` to wait for results.
+    pub async fn get_batch(&self, batch_id: &str) -> Result<MessageBatch> {
+        let response = self
+            .send(self.get(&format!("/v1/messages/batches/{batch_id}"))?)
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// Lists batches, most recently created first. Pages are fetched as the stream is consumed.
+    pub fn list_batches(&self, params: ListParams) -> Paginated<MessageBatch> {
+        self.paginate("/v1/messages/batches", params, &[])
+    }
+
+    /// Cancels a batch. Requests that are already being processed will still complete.
+    pub async fn cancel_batch(&self, batch_id: &str) -> Result<MessageBatch> {
+        let response = self
+            .send(self.post(&format!("/v1/messages/batches/{batch_id}/cancel"))?)
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// Deletes a batch. Batches must finish processing before they can be deleted.
+    pub async fn delete_batch(&self, batch_id: &str) -> Result<DeletedBatch> {
+        let response = self
+            .send(self.delete(&format!("/v1/messages/batches/{batch_id}"))?)
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// Streams the results of a batch that has finished processing. The results file is read
+    /// incrementally, one line at a time. Results are not guaranteed to be in request order, so
+    /// use `custom_id` to match them up. The client's request timeout does not apply, since
+    /// large results files may take a long time to download.
+    pub async fn batch_results(&self, batch_id: &str) -> Result<BatchResults> {
+        let response = self
+            .send_without_timeout(self.get(&format!("/v1/messages/batches/{batch_id}/results"))?)
+            .await?;
+        Ok(json_lines(response))
+    }
+}
+
+/// Parses a JSON Lines response body into a stream of values as it is downloaded.
+fn json_lines<T>(response: reqwest::Response) -> Pin<Box<dyn Stream<Item = Result<T>> + Send>>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let state = (Box::pin(response.bytes_stream()), Vec::new(), false);
+    Box::pin(stream::try_unfold(
+        state,
+        |(mut body, mut buffer, mut eof)| async move {
+            loop {
+                let line: Vec<u8> = match buffer.iter().position(|b| *b == b'\n') {
+                    Some(end) => buffer.drain(..=end).collect(),
+                    // The last line may not be terminated by a newline.
+                    None if eof => std::mem::take(&mut buffer),
+                    None => {
+                        match body.next().await {
+                            Some(chunk) => buffer.extend_from_slice(&chunk?),
+                            None => eof = true,
+                        }
+                        continue;
+                    }
+                };
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    if eof && buffer.is_empty() {
+                        return Ok(None);
+                    }
+                    continue;
+                }
+                let value = serde_json::from_slice(&line)?;
+                return Ok(Some((value, (body, buffer, eof))));
+            }
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::TryStreamExt;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        tests::{message, test_client},
+        Content, Error, RetryPolicy, DEFAULT_MODEL,
+    };
+
+    #[tokio::test]
+    async fn test_batches() {
+        let server = MockServer::start().await;
+        let batch = json!({
+            "id": "msgbatch_01",
+            "type": "message_batch",
+            "processing_status": "in_progress",
+            "request_counts": {"processing": 2, "succeeded": 0, "errored": 0, "canceled": 0, "expired": 0},
+            "created_at": "2025-06-01T00:00:00Z",
+            "expires_at": "2025-06-02T00:00:00Z",
+            "ended_at": null,
+            "archived_at": null,
+            "cancel_initiated_at": null,
+            "results_url": null
+        });
+        Mock::given(method("POST"))
+            .and(path("/v1/messages/batches"))
+            .and(body_json(json!({"requests": [
+                {
+                    "custom_id": "first",
+                    "params": {
+                        "model": DEFAULT_MODEL,
+                        "max_tokens": 1024,
+                        "messages": [{"role": "user", "content": [{"type": "text", "text": "Hi"}]}],
+                        "stream": false
+                    }
+                }
+            ]})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&batch))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let results = [
+            json!({"custom_id": "first", "result": {
+                "type": "succeeded",
+                "message": message(json!([{"type": "text", "text": "Hello"}]), "end_turn")
+            }}),
+            json!({"custom_id": "second", "result": {"type": "errored", "error": {
+                "type": "error",
+                "error": {"type": "invalid_request_error", "message": "Bad"}
+            }}}),
+            json!({"custom_id": "third", "result": {"type": "expired"}}),
+        ]
+        .iter()
+        .map(|r| r.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+        Mock::given(method("GET"))
+            .and(path("/v1/messages/batches/msgbatch_01/results"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(results))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = test_client(&server);
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::text("Hi"));
+        let batch = client
+            .create_batch(&[("first".to_string(), request)])
+            .await
+            .unwrap();
+        assert_eq!(batch.processing_status, BatchStatus::InProgress);
+        assert_eq!(batch.request_counts.processing, 2);
+
+        let results: Vec<BatchResult> = client
+            .batch_results(&batch.id)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        match &results[0].result {
+            BatchOutcome::Succeeded { message } => {
+                assert_eq!(message.format_content(), "Hello")
+            }
+            other => panic!("Expected success, got {other:?}"),
+        }
+        assert!(matches!(results[1].result, BatchOutcome::Errored { .. }));
+        assert_eq!(results[2].custom_id, "third");
+        assert!(matches!(results[2].result, BatchOutcome::Expired));
+    }
+
+    #[tokio::test]
+    async fn test_batch_results_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(
+                        json!({"custom_id": "first", "result": {"type": "expired"}}).to_string(),
+                    )
+                    .set_delay(Duration::from_millis(300)),
+            )
+            .mount(&server)
+            .await;
+
+        // The total timeout applies to other requests, but not to downloading results.
+        let client = Anthropic::builder()
+            .with_api_key("key")
+            .with_base_url(server.uri())
+            .with_timeout(Duration::from_millis(100))
+            .with_retry_policy(RetryPolicy::none())
+            .build()
+            .unwrap();
+        assert!(matches!(
+            client.get_batch("msgbatch_01").await,
+            Err(Error::HttpError(e)) if e.is_timeout()
+        ));
+        let results: Vec<BatchResult> = client
+            .batch_results("msgbatch_01")
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+    }
+}
@@ -0,0 +1,330 @@
+//! Citations linking generated text back to the documents and search results it is based on.
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Content, Document, MessagesRequest, MessagesResponse, SearchResult, ToolResult,
+    ToolResultContent,
+};
+
+/// A reference from a text block to the passage of a source that supports it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Citation {
+    /// A character range in a plain text document.
+    CharLocation {
+        /// The text being cited.
+        cited_text: String,
+        /// Index of the document among all documents in the request.
+        document_index: usize,
+        /// The title of the cited document, if it has one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        document_title: Option<String>,
+        /// Index of the first cited character.
+        start_char_index: usize,
+        /// Index one past the last cited character.
+        end_char_index: usize,
+        /// The Files API id of the cited document, if it came from an uploaded file.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_id: Option<String>,
+    },
+    /// A page range in a PDF document.
+    PageLocation {
+        /// The text being cited.
+        cited_text: String,
+        /// Index of the document among all documents in the request.
+        document_index: usize,
+        /// The title of the cited document, if it has one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        document_title: Option<String>,
+        /// The first cited page, starting from 1.
+        start_page_number: usize,
+        /// The page after the last cited page.
+        end_page_number: usize,
+        /// The Files API id of the cited document, if it came from an uploaded file.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_id: Option<String>,
+    },
+    /// A range of content blocks in a custom content document.
+    ContentBlockLocation {
+        /// The text being cited.
+        cited_text: String,
+        /// Index of the document among all documents in the request.
+        document_index: usize,
+        /// The title of the cited document, if it has one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        document_title: Option<String>,
+        /// Index of the first cited block.
+        start_block_index: usize,
+        /// Index one past the last cited block.
+        end_block_index: usize,
+        /// The Files API id of the cited document, if it came from an uploaded file.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_id: Option<String>,
+    },
+    /// A result from the web search tool.
+    WebSearchResultLocation {
+        /// The text being cited.
+        cited_text: String,
+        /// URL of the cited page.
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        /// Opaque reference to the search result, which must be passed back unchanged.
+        encrypted_index: String,
+    },
+    /// A range of content blocks in a search result content block.
+    SearchResultLocation {
+        /// The text being cited.
+        cited_text: String,
+        /// The source of the search result, e.g. a URL.
+        source: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        /// Index of the search result among all search results in the request.
+        search_result_index: usize,
+        /// Index of the first cited block.
+        start_block_index: usize,
+        /// Index one past the last cited block.
+        end_block_index: usize,
+    },
+}
+
+/// The cited part of a source, as a half-open range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CitedSpan {
+    /// Character indices in a plain text document.
+    Chars(Range<usize>),
+    /// Page numbers in a PDF, starting from 1.
+    Pages(Range<usize>),
+    /// Content block indices in a custom content document or search result.
+    Blocks(Range<usize>),
+}
+
+impl Citation {
+    /// The text being cited.
+    pub fn cited_text(&self) -> &str {
+        match self {
+            Self::CharLocation { cited_text, .. }
+            | Self::PageLocation { cited_text, .. }
+            | Self::ContentBlockLocation { cited_text, .. }
+            | Self::WebSearchResultLocation { cited_text, .. }
+            | Self::SearchResultLocation { cited_text, .. } => cited_text,
+        }
+    }
+
+    /// Index of the cited document among all documents in the request, if the citation refers
+    /// to a document.
+    pub fn document_index(&self) -> Option<usize> {
+        match self {
+            Self::CharLocation { document_index, .. }
+            | Self::PageLocation { document_index, .. }
+            | Self::ContentBlockLocation { document_index, .. } => Some(*document_index),
+            _ => None,
+        }
+    }
+
+    /// The title of the cited source, if known.
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            Self::CharLocation { document_title, .. }
+            | Self::PageLocation { document_title, .. }
+            | Self::ContentBlockLocation { document_title, .. } => document_title.as_deref(),
+            Self::WebSearchResultLocation { title, .. }
+            | Self::SearchResultLocation { title, .. } => title.as_deref(),
+        }
+    }
+
+    /// The cited part of the source. Web search results don't have a span.
+    pub fn span(&self) -> Option<CitedSpan> {
+        match self {
+            Self::CharLocation {
+                start_char_index,
+                end_char_index,
+                ..
+            } => Some(CitedSpan::Chars(*start_char_index..*end_char_index)),
+            Self::PageLocation {
+                start_page_number,
+                end_page_number,
+                ..
+            } => Some(CitedSpan::Pages(*start_page_number..*end_page_number)),
+            Self::ContentBlockLocation {
+                start_block_index,
+                end_block_index,
+                ..
+            }
+            | Self::SearchResultLocation {
+                start_block_index,
+                end_block_index,
+                ..
+            } => Some(CitedSpan::Blocks(*start_block_index..*end_block_index)),
+            Self::WebSearchResultLocation { .. } => None,
+        }
+    }
+}
+
+/// A citation together with the source it refers to, as returned by
+/// `MessagesRequest::resolve_citations`.
+#[derive(Debug, Clone)]
+pub struct ResolvedCitation<'a> {
+    /// Index of the response content block containing the citation.
+    pub block_index: usize,
+    /// The citation, as returned by the API.
+    pub citation: &'a Citation,
+    /// The cited document, if the citation refers to a document in the request.
+    pub document: Option<&'a Document>,
+    /// The cited search result, if the citation refers to a search result in the request.
+    pub search_result: Option<&'a SearchResult>,
+}
+
+impl ResolvedCitation<'_> {
+    /// The title of the cited source, falling back to the title set on the document.
+    pub fn title(&self) -> Option<&str> {
+        self.citation
+            .title()
+            .or_else(|| self.document.and_then(|d| d.title.as_deref()))
+            .or_else(|| self.search_result.map(|r| r.title.as_str()))
+    }
+}
+
+impl MessagesRequest {
+    /// The content blocks of all messages, including blocks nested in tool results.
+    fn nested_content(&self) -> impl Iterator<Item = &Content> {
+        self.messages
+            .iter()
+            .flat_map(|m| &m.content)
+            .flat_map(|content| match content {
+                Content::ToolResult(ToolResult {
+                    content: ToolResultContent::Blocks(blocks),
+                    ..
+                }) => blocks.iter().collect(),
+                content => vec![content],
+            })
+    }
+
+    /// The documents in the request, in the order used by `Citation::document_index`.
+    pub fn documents(&self) -> Vec<&Document> {
+        self.nested_content()
+            .filter_map(|content| match content {
+                Content::Document(document) => Some(document),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The search results in the request, in the order used by the `search_result_index` of
+    /// search result citations.
+    pub fn search_results(&self) -> Vec<&SearchResult> {
+        self.nested_content()
+            .filter_map(|content| match content {
+                Content::SearchResult(result) => Some(result),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Resolves every citation in a response to this request back to its source document.
+    pub fn resolve_citations<'a>(
+        &'a self,
+        response: &'a MessagesResponse,
+    ) -> Vec<ResolvedCitation<'a>> {
+        let documents = self.documents();
+        let search_results = self.search_results();
+        let mut resolved = Vec::new();
+        for (block_index, content) in response.content.iter().enumerate() {
+            let Content::Text(text) = content else {
+                continue;
+            };
+            for citation in text.citations.iter().flatten() {
+                resolved.push(ResolvedCitation {
+                    block_index,
+                    citation,
+                    document: citation
+                        .document_index()
+                        .and_then(|i| documents.get(i).copied()),
+                    search_result: match citation {
+                        Citation::SearchResultLocation {
+                            search_result_index,
+                            ..
+                        } => search_results.get(*search_result_index).copied(),
+                        _ => None,
+                    },
+                });
+            }
+        }
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::tests::message;
+
+    #[test]
+    fn test_resolve_citations() {
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::text_document("Ignore me."));
+        request.add_user(Content::Document(
+            Document::new(crate::DocumentSource::Text {
+                media_type: "text/plain".into(),
+                data: "The grass is green. The sky is blue.".into(),
+            })
+            .with_title("Facts")
+            .with_citations(true),
+        ));
+        request.add_user(Content::text("What color is the sky?"));
+
+        let response: MessagesResponse = serde_json::from_value(message(
+            json!([
+                {"type": "text", "text": "According to the document, "},
+                {"type": "text", "text": "the sky is blue", "citations": [{
+                    "type": "char_location",
+                    "cited_text": "The sky is blue.",
+                    "document_index": 1,
+                    "start_char_index": 20,
+                    "end_char_index": 36
+                }]}
+            ]),
+            "end_turn",
+        ))
+        .unwrap();
+
+        let resolved = request.resolve_citations(&response);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].block_index, 1);
+        assert_eq!(resolved[0].title(), Some("Facts"));
+        assert_eq!(resolved[0].citation.cited_text(), "The sky is blue.");
+        assert_eq!(resolved[0].citation.span(), Some(CitedSpan::Chars(20..36)));
+        assert!(resolved[0].document.is_some_and(|d| d.title.is_some()));
+
+        // Search results returned by tools can be cited too.
+        let tool_use = crate::ToolUse::new("toolu_01".into(), "search".into(), json!({}));
+        request.add_user(Content::tool_result_blocks(
+            &tool_use,
+            vec![Content::SearchResult(
+                SearchResult::new("https://example.com/sky", "Sky", ["The sky is blue."])
+                    .with_citations(true),
+            )],
+        ));
+        let response: MessagesResponse = serde_json::from_value(message(
+            json!([{"type": "text", "text": "It's blue", "citations": [{
+                "type": "search_result_location",
+                "cited_text": "The sky is blue.",
+                "source": "https://example.com/sky",
+                "search_result_index": 0,
+                "start_block_index": 0,
+                "end_block_index": 1
+            }]}]),
+            "end_turn",
+        ))
+        .unwrap();
+        let resolved = request.resolve_citations(&response);
+        assert_eq!(resolved[0].title(), Some("Sky"));
+        assert!(resolved[0].document.is_none());
+        assert_eq!(resolved[0].citation.span(), Some(CitedSpan::Blocks(0..1)));
+    }
+}
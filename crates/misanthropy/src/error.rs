@@ -1,17 +1,23 @@
+use std::{fmt, time::Duration};
+
+use reqwest::header::HeaderMap;
 use thiserror::Error;
 
-use crate::{ApiErrorResponse, ApiErrorType};
+use crate::{retry, ApiErrorResponse, ApiErrorType, StreamError};
 
 /// Convenience type alias for Results using the crate's Error type.
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Errors encountered during Anthropic API interactions.
 /// Covers API responses, HTTP issues, parsing failures, and client-side problems.
+///
+/// Errors returned by the API carry an [`ApiErrorDetails`] with the HTTP status, request id and
+/// raw response body.
 #[derive(Error, Debug)]
 pub enum Error {
-    /// API request failed with an error message.
+    /// An unexpected error internal to Anthropic's systems (HTTP 500 and other 5xx statuses).
     #[error("API request failed: {0}")]
-    ApiError(String),
+    ApiError(Box<ApiErrorDetails>),
 
     /// Failed to parse the API response JSON.
     #[error("Failed to parse API response: {0}")]
@@ -21,25 +27,33 @@ pub enum Error {
     #[error("HTTP request failed: {0}")]
     HttpError(#[from] reqwest::Error),
 
-    /// Rate limit exceeded for the API.
+    /// Rate limit exceeded for the API (HTTP 429).
     #[error("Rate limit exceeded: {0}")]
-    RateLimitExceeded(String),
+    RateLimitExceeded(Box<ApiErrorDetails>),
 
-    /// API is temporarily overloaded.
+    /// API is temporarily overloaded (HTTP 529).
     #[error("API overloaded: {0}")]
-    ApiOverloaded(String),
+    ApiOverloaded(Box<ApiErrorDetails>),
 
-    /// An unknown error occurred.
+    /// The API returned an error that doesn't fit any other category.
     #[error("Unknown error: {0}")]
     UnknownError(String),
 
-    /// Authentication or permission error.
+    /// Authentication or permission error (HTTP 401 or 403).
     #[error("Unauthorized: {0}")]
-    Unauthorized(String),
+    Unauthorized(Box<ApiErrorDetails>),
 
-    /// Invalid request format or content.
+    /// The API rejected the format or content of the request (HTTP 400 or 413).
     #[error("Bad request: {0}")]
-    BadRequest(String),
+    BadRequest(Box<ApiErrorDetails>),
+
+    /// The requested resource was not found (HTTP 404).
+    #[error("Not found: {0}")]
+    NotFound(Box<ApiErrorDetails>),
+
+    /// The request was rejected before being sent to the API.
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
 
     /// Environment variable not found.
     #[error("Environment variable not found: {0}")]
@@ -53,24 +67,342 @@ pub enum Error {
     #[error("Stream error: {0}")]
     StreamError(String),
 
+    /// Streamed tool input could not be parsed as JSON.
+    #[error("Failed to parse tool input for content block {index}: {source}")]
+    ToolInputParseError {
+        /// The index of the tool use content block.
+        index: usize,
+        /// The underlying JSON parse error.
+        source: serde_json::Error,
+    },
+
     /// Invalid HTTP header value.
     #[error("Invalid header value: {0}")]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
+
+    /// Invalid HTTP header name.
+    #[error("Invalid header name: {0}")]
+    InvalidHeaderName(#[from] reqwest::header::InvalidHeaderName),
+
+    /// A local MCP server failed to start, closed its connection or returned an error.
+    #[error("MCP error: {0}")]
+    McpError(String),
+
+    /// Communicating with a local process failed.
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// `Anthropic::extract` didn't get valid data from the AI within the allowed attempts.
+    #[error("Extraction failed after {attempts} attempts: {message}")]
+    ExtractionError {
+        /// The number of requests made.
+        attempts: u32,
+        /// Why the last attempt failed.
+        message: String,
+    },
+}
+
+impl Error {
+    /// Details of the API error response, if this error was returned by the API.
+    pub fn api_details(&self) -> Option<&ApiErrorDetails> {
+        match self {
+            Error::ApiError(details)
+            | Error::RateLimitExceeded(details)
+            | Error::ApiOverloaded(details)
+            | Error::Unauthorized(details)
+            | Error::BadRequest(details)
+            | Error::NotFound(details) => Some(details),
+            _ => None,
+        }
+    }
+
+    /// The HTTP status of the API error response, if any.
+    pub fn status(&self) -> Option<u16> {
+        self.api_details().map(|d| d.status)
+    }
+
+    /// The `request-id` of the failed request, if the API returned one.
+    pub fn request_id(&self) -> Option<&str> {
+        self.api_details().and_then(|d| d.request_id.as_deref())
+    }
+
+    /// How long the API asked us to wait before retrying, if it said.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.api_details().and_then(|d| d.retry_after)
+    }
+
+    /// Is this error transient, so that the same request may succeed if retried? True for rate
+    /// limits, overloads, server errors and connection failures.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::RateLimitExceeded(_) | Error::ApiOverloaded(_) => true,
+            Error::ApiError(details) => details.status >= 500,
+            Error::HttpError(e) => is_connection_error(e),
+            _ => false,
+        }
+    }
+
+    /// Builds an error from the status, headers and body of an unsuccessful API response. Bodies
+    /// that aren't a JSON error object, such as HTML from a proxy or an empty 502, fall back to
+    /// an error type derived from the status code.
+    pub(crate) fn from_api_response(status: u16, headers: &HeaderMap, body: String) -> Self {
+        let (error_type, message) = match serde_json::from_str::<ApiErrorResponse>(&body) {
+            Ok(response) => (response.error.error_type, response.error.message),
+            Err(_) => {
+                let message = if body.trim().is_empty() {
+                    reqwest::StatusCode::from_u16(status)
+                        .ok()
+                        .and_then(|s| s.canonical_reason())
+                        .unwrap_or("no response body")
+                        .to_string()
+                } else {
+                    body.trim().to_string()
+                };
+                (ApiErrorType::from_status(status), message)
+            }
+        };
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        ApiErrorDetails {
+            status,
+            error_type,
+            message,
+            request_id: header("request-id"),
+            retry_after: retry::retry_after(headers),
+            rate_limits: RateLimits::from_headers(headers),
+            body,
+        }
+        .into()
+    }
+
+    /// Reads an unsuccessful API response into an error.
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        match response.text().await {
+            Ok(body) => Self::from_api_response(status, &headers, body),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+/// Is this a failure to connect to or communicate with the API, rather than an error response?
+pub(crate) fn is_connection_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout() || error.is_request()
+}
+
+/// Details of an error response from the Anthropic API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiErrorDetails {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The type of error reported by the API, or derived from the status code if the response
+    /// body couldn't be parsed.
+    pub error_type: ApiErrorType,
+    /// A human-readable description of the error.
+    pub message: String,
+    /// The `request-id` header. Include this when reporting problems to Anthropic.
+    pub request_id: Option<String>,
+    /// How long the API asked us to wait before retrying, from the `retry-after` header.
+    pub retry_after: Option<Duration>,
+    /// Rate limit state reported in the `anthropic-ratelimit-*` headers.
+    pub rate_limits: RateLimits,
+    /// The raw response body.
+    pub body: String,
+}
+
+impl fmt::Display for ApiErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request-id: {request_id})")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ApiErrorDetails> for Error {
+    fn from(details: ApiErrorDetails) -> Self {
+        let details = Box::new(details);
+        match details.error_type {
+            ApiErrorType::InvalidRequestError | ApiErrorType::RequestTooLarge => {
+                Error::BadRequest(details)
+            }
+            ApiErrorType::AuthenticationError | ApiErrorType::PermissionError => {
+                Error::Unauthorized(details)
+            }
+            ApiErrorType::NotFoundError => Error::NotFound(details),
+            ApiErrorType::RateLimitError => Error::RateLimitExceeded(details),
+            ApiErrorType::ApiError => Error::ApiError(details),
+            ApiErrorType::OverloadedError => Error::ApiOverloaded(details),
+            ApiErrorType::Other => match ApiErrorType::from_status(details.status) {
+                ApiErrorType::Other => match details.status {
+                    400..=499 => Error::BadRequest(details),
+                    500..=599 => Error::ApiError(details),
+                    _ => Error::UnknownError(details.to_string()),
+                },
+                error_type => ApiErrorDetails {
+                    error_type,
+                    ..*details
+                }
+                .into(),
+            },
+        }
+    }
+}
+
+/// Rate limit state reported by the API in `anthropic-ratelimit-*` headers. Reset times are
+/// RFC 3339 timestamps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    /// Maximum requests allowed in the current period.
+    pub requests_limit: Option<u64>,
+    /// Requests remaining in the current period.
+    pub requests_remaining: Option<u64>,
+    /// When the request limit will be fully replenished.
+    pub requests_reset: Option<String>,
+    /// Maximum tokens allowed in the current period.
+    pub tokens_limit: Option<u64>,
+    /// Tokens remaining in the current period.
+    pub tokens_remaining: Option<u64>,
+    /// When the token limit will be fully replenished.
+    pub tokens_reset: Option<String>,
+    /// Maximum input tokens allowed in the current period.
+    pub input_tokens_limit: Option<u64>,
+    /// Input tokens remaining in the current period.
+    pub input_tokens_remaining: Option<u64>,
+    /// When the input token limit will be fully replenished.
+    pub input_tokens_reset: Option<String>,
+    /// Maximum output tokens allowed in the current period.
+    pub output_tokens_limit: Option<u64>,
+    /// Output tokens remaining in the current period.
+    pub output_tokens_remaining: Option<u64>,
+    /// When the output token limit will be fully replenished.
+    pub output_tokens_reset: Option<String>,
+}
+
+impl RateLimits {
+    /// Reads rate limit state from response headers.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let string = |name: &str| {
+            headers
+                .get(format!("anthropic-ratelimit-{name}"))
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        let number = |name: &str| string(name).and_then(|v| v.parse().ok());
+        Self {
+            requests_limit: number("requests-limit"),
+            requests_remaining: number("requests-remaining"),
+            requests_reset: string("requests-reset"),
+            tokens_limit: number("tokens-limit"),
+            tokens_remaining: number("tokens-remaining"),
+            tokens_reset: string("tokens-reset"),
+            input_tokens_limit: number("input-tokens-limit"),
+            input_tokens_remaining: number("input-tokens-remaining"),
+            input_tokens_reset: string("input-tokens-reset"),
+            output_tokens_limit: number("output-tokens-limit"),
+            output_tokens_remaining: number("output-tokens-remaining"),
+            output_tokens_reset: string("output-tokens-reset"),
+        }
+    }
 }
 
 impl From<ApiErrorResponse> for Error {
     fn from(error: ApiErrorResponse) -> Self {
-        match error.error.error_type {
-            ApiErrorType::InvalidRequestError => Error::BadRequest(error.error.message),
-            ApiErrorType::AuthenticationError => Error::Unauthorized(error.error.message),
-            ApiErrorType::PermissionError => Error::Unauthorized(error.error.message),
-            ApiErrorType::RateLimitError => Error::RateLimitExceeded(error.error.message),
-            ApiErrorType::NotFoundError => {
-                Error::UnknownError(format!("Not found: {}", error.error.message))
-            }
-            ApiErrorType::ApiError => Error::ApiError(error.error.message),
-            ApiErrorType::OverloadedError => Error::ApiOverloaded(error.error.message),
-            ApiErrorType::Other => Error::UnknownError(error.error.message),
+        ApiErrorDetails {
+            status: error.error.error_type.status(),
+            error_type: error.error.error_type,
+            message: error.error.message,
+            request_id: None,
+            retry_after: None,
+            rate_limits: RateLimits::default(),
+            body: String::new(),
+        }
+        .into()
+    }
+}
+
+/// Converts an `error` event received partway through a streamed response.
+impl From<StreamError> for Error {
+    fn from(error: StreamError) -> Self {
+        let error_type = serde_json::from_value(serde_json::Value::String(error.type_.clone()))
+            .unwrap_or(ApiErrorType::Other);
+        let body = serde_json::json!({
+            "type": "error",
+            "error": {"type": error.type_, "message": error.message}
+        })
+        .to_string();
+        ApiErrorDetails {
+            status: error_type.status(),
+            error_type,
+            message: error.message,
+            request_id: None,
+            retry_after: None,
+            rate_limits: RateLimits::default(),
+            body,
         }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_from_api_response() {
+        let mut headers = HeaderMap::new();
+        headers.insert("request-id", HeaderValue::from_static("req_01"));
+        headers.insert("retry-after", HeaderValue::from_static("5"));
+        headers.insert(
+            "anthropic-ratelimit-requests-remaining",
+            HeaderValue::from_static("0"),
+        );
+        let body = r#"{"type":"error","error":{"type":"rate_limit_error","message":"Slow down"}}"#;
+        let err = Error::from_api_response(429, &headers, body.into());
+        assert!(matches!(err, Error::RateLimitExceeded(_)));
+        assert!(err.is_retryable());
+        assert_eq!(err.status(), Some(429));
+        assert_eq!(err.request_id(), Some("req_01"));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(5)));
+        let details = err.api_details().unwrap();
+        assert_eq!(details.rate_limits.requests_remaining, Some(0));
+        assert_eq!(details.body, body);
+        assert_eq!(
+            err.to_string(),
+            "Rate limit exceeded: Slow down (request-id: req_01)"
+        );
+
+        let err = Error::from_api_response(
+            404,
+            &HeaderMap::new(),
+            r#"{"type":"error","error":{"type":"not_found_error","message":"No such model"}}"#
+                .into(),
+        );
+        assert!(matches!(err, Error::NotFound(_)));
+        assert!(!err.is_retryable());
+
+        // Bodies that aren't JSON fall back to the status code.
+        let err =
+            Error::from_api_response(502, &HeaderMap::new(), "<html>Bad Gateway</html>".into());
+        assert!(matches!(err, Error::ApiError(_)));
+        assert!(err.is_retryable());
+        assert_eq!(
+            err.api_details().unwrap().message,
+            "<html>Bad Gateway</html>"
+        );
+
+        let err = Error::from_api_response(503, &HeaderMap::new(), String::new());
+        assert_eq!(err.api_details().unwrap().message, "Service Unavailable");
+
+        let err = Error::from_api_response(418, &HeaderMap::new(), String::new());
+        assert!(matches!(err, Error::BadRequest(_)));
+        assert_eq!(err.api_details().unwrap().error_type, ApiErrorType::Other);
     }
 }
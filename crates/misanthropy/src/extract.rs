@@ -0,0 +1,259 @@
+//! Structured output: extracting typed data from a response by forcing the AI to call a tool.
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+
+use crate::{Anthropic, Content, Error, MessagesRequest, Result, StopReason, Tool, ToolChoice};
+
+impl Anthropic {
+    /// Extracts data of type `T` from the conversation in `request`.
+    ///
+    /// `T` is added to the request as a tool, which the AI is forced to call, and the tool input
+    /// is deserialized into `T`. The doc comments of `T` and its fields describe the tool to the
+    /// AI. If the input can't be deserialized into `T`, the error is sent back to the AI as an
+    /// error tool result, and it tries again, up to `max_retries` times. Only deserialization
+    /// errors cause a retry: schema constraints that serde doesn't enforce, such as `minimum` or
+    /// `pattern`, are not checked. Responses cut short by `max_tokens` are not retried.
+    ///
+    /// The tool is named after `T`, and the request must not already have a tool with that name.
+    /// Forcing a tool can't be combined with extended thinking.
+    ///
+    /// ```no_run
+    /// # use misanthropy::{Anthropic, Content, MessagesRequest};
+    /// # use schemars::JsonSchema;
+    /// # use serde::Deserialize;
+    /// /// Record the sentiment of a review.
+    /// #[derive(JsonSchema, Deserialize)]
+    /// struct Sentiment {
+    ///     /// Between -1 (very negative) and 1 (very positive).
+    ///     score: f64,
+    /// }
+    ///
+    /// # async fn example(client: Anthropic) -> misanthropy::Result<()> {
+    /// let mut request = MessagesRequest::default();
+    /// request.add_user(Content::text("Review: The battery died after a week."));
+    /// let sentiment: Sentiment = client.extract(&request, 2).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn extract<T: JsonSchema + DeserializeOwned>(
+        &self,
+        request: &MessagesRequest,
+        max_retries: u32,
+    ) -> Result<T> {
+        let name = tool_name::<T>();
+        if request.tools.iter().any(|tool| tool.name() == name) {
+            return Err(Error::InvalidRequest(format!(
+                "The request already has a tool named {name}, which extract needs for {}",
+                std::any::type_name::<T>()
+            )));
+        }
+        let mut request = request
+            .clone()
+            .with_tool(Tool::custom::<T>(&name)?)
+            .with_tool_choice(ToolChoice::tool(&name))
+            .with_disable_parallel_tool_use(true);
+
+        let mut attempts = 0;
+        loop {
+            let response = self.messages(&request).await?;
+            attempts += 1;
+            let tool_use = response.tool_uses().find(|tool_use| tool_use.name == name);
+            let message = match tool_use {
+                Some(tool_use) => match serde_json::from_value(tool_use.input.clone()) {
+                    Ok(value) => return Ok(value),
+                    Err(e) => format!("Invalid input for tool {name}: {e}"),
+                },
+                None => format!(
+                    "The model did not call the {name} tool (stop reason {:?})",
+                    response.stop_reason
+                ),
+            };
+            if response.stop_reason == Some(StopReason::MaxTokens) {
+                // A retry would be cut short in the same way.
+                let message = match tool_use {
+                    Some(_) => format!("The response reached max_tokens. {message}"),
+                    None => message,
+                };
+                return Err(Error::ExtractionError { attempts, message });
+            }
+            if attempts > max_retries {
+                return Err(Error::ExtractionError { attempts, message });
+            }
+
+            request.merge_response(&response);
+            match tool_use {
+                Some(tool_use) => request.add_user(Content::tool_error(
+                    tool_use,
+                    format!("{message}. Call {name} again with corrected input."),
+                )),
+                None => {
+                    // Every tool use needs a result before the conversation can continue.
+                    for tool_use in response.tool_uses() {
+                        request.add_user(Content::tool_error(
+                            tool_use,
+                            format!("Only the {name} tool is available."),
+                        ));
+                    }
+                    request.add_user(Content::text(format!("Call the {name} tool.")));
+                }
+            }
+        }
+    }
+}
+
+/// A valid tool name for `T`, derived from its schema name.
+fn tool_name<T: JsonSchema>() -> String {
+    let name: String = T::schema_name()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect();
+    if name.is_empty() {
+        "extract".into()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+    use wiremock::{
+        matchers::{body_partial_json, method},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::tests::{message_response, test_client};
+
+    /// Record the sentiment of a review.
+    #[derive(JsonSchema, Deserialize, Debug, PartialEq)]
+    struct Sentiment {
+        label: Label,
+        /// How confident the label is, from 0 to 1.
+        confidence: f64,
+    }
+
+    #[derive(JsonSchema, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Label {
+        Positive,
+        Negative,
+    }
+
+    fn tool_use(input: Value) -> ResponseTemplate {
+        message_response(
+            json!([{"type": "tool_use", "id": "toolu_01", "name": "Sentiment", "input": input}]),
+            "tool_use",
+        )
+    }
+
+    #[tokio::test]
+    async fn test_extract() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                json!({"messages": [{}, {}, {"content": [{
+                    "type": "tool_result",
+                    "tool_use_id": "toolu_01",
+                    "is_error": true
+                }]}]}),
+            ))
+            .respond_with(tool_use(json!({"label": "negative", "confidence": 0.9})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "tools": [{"name": "Sentiment", "description": "Record the sentiment of a review."}],
+                "tool_choice": {"type": "tool", "name": "Sentiment", "disable_parallel_tool_use": true}
+            })))
+            .respond_with(tool_use(json!({"label": "sad", "confidence": 0.9})))
+            .mount(&server)
+            .await;
+
+        let client = test_client(&server);
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::text("Review: The battery died after a week."));
+
+        let sentiment: Sentiment = client.extract(&request, 1).await.unwrap();
+        assert_eq!(
+            sentiment,
+            Sentiment {
+                label: Label::Negative,
+                confidence: 0.9
+            }
+        );
+
+        let err = client.extract::<Sentiment>(&request, 0).await.unwrap_err();
+        match err {
+            Error::ExtractionError { attempts, message } => {
+                assert_eq!(attempts, 1);
+                assert!(message.starts_with("Invalid input for tool Sentiment: unknown variant"));
+            }
+            _ => panic!("Expected Error::ExtractionError"),
+        }
+
+        let request = request.with_tool(Tool::custom::<Sentiment>("Sentiment").unwrap());
+        let err = client.extract::<Sentiment>(&request, 1).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn test_extract_without_tool_call() {
+        let server = MockServer::start().await;
+        // Other tool uses are answered with errors before asking again.
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({"messages": [{}, {}, {"content": [
+                {"type": "tool_result", "tool_use_id": "toolu_02", "is_error": true},
+                {"type": "text", "text": "Call the Sentiment tool."}
+            ]}]})))
+            .respond_with(tool_use(json!({"label": "positive", "confidence": 0.5})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(message_response(
+                json!([{"type": "tool_use", "id": "toolu_02", "name": "lookup", "input": {}}]),
+                "tool_use",
+            ))
+            .mount(&server)
+            .await;
+
+        let client = test_client(&server);
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::text("Review: Works great."));
+        let sentiment: Sentiment = client.extract(&request, 1).await.unwrap();
+        assert_eq!(sentiment.label, Label::Positive);
+
+        // Responses cut short by max_tokens are not retried.
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(message_response(
+                json!([{"type": "tool_use", "id": "toolu_01", "name": "Sentiment",
+                        "input": {"label": "positive"}}]),
+                "max_tokens",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let err = test_client(&server)
+            .extract::<Sentiment>(&request, 3)
+            .await
+            .unwrap_err();
+        match err {
+            Error::ExtractionError { attempts, message } => {
+                assert_eq!(attempts, 1);
+                assert!(message.starts_with("The response reached max_tokens."));
+            }
+            _ => panic!("Expected Error::ExtractionError"),
+        }
+    }
+}
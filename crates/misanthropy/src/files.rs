@@ -0,0 +1,174 @@
+//! The Files API, for uploading files once and referencing them from messages by id.
+use std::path::Path;
+
+use reqwest::{multipart, Method};
+use serde::{Deserialize, Serialize};
+
+use crate::{detect_media_type, Anthropic, ListParams, Paginated, Result, FILES_API_BETA};
+
+/// Metadata for an uploaded file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    /// Unique identifier for the file, used to reference it in messages.
+    pub id: String,
+    /// Always "file".
+    #[serde(rename = "type")]
+    pub file_type: String,
+    /// The original name of the uploaded file.
+    pub filename: String,
+    /// MIME type of the file.
+    pub mime_type: String,
+    /// Size of the file in bytes.
+    pub size_bytes: u64,
+    /// RFC 3339 timestamp of when the file was created.
+    pub created_at: String,
+    /// Whether the file content can be downloaded. Only files created by tools, such as code
+    /// execution, are downloadable.
+    #[serde(default)]
+    pub downloadable: bool,
+}
+
+/// Confirmation that a file was deleted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletedFile {
+    /// The id of the deleted file.
+    pub id: String,
+    /// Always "file_deleted".
+    #[serde(rename = "type")]
+    pub file_type: String,
+}
+
+impl Anthropic {
+    /// Uploads a file from disk. The media type is guessed from the file extension.
+    pub async fn upload_file(&self, path: impl AsRef<Path>) -> Result<FileMetadata> {
+        let path = path.as_ref();
+        let data = tokio::fs::read(path)
+            .await
+            .map_err(|e| crate::Error::InvalidRequest(format!("{}: {e}", path.display())))?;
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".into());
+        self.upload_file_bytes(filename, &detect_media_type(path), data)
+            .await
+    }
+
+    /// Uploads a file from memory with the given name and media type, e.g. `application/pdf`.
+    ///
+    /// Uploads are not retried, since the request body can't be replayed.
+    pub async fn upload_file_bytes(
+        &self,
+        filename: impl Into<String>,
+        media_type: &str,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<FileMetadata> {
+        let part = multipart::Part::bytes(data.into())
+            .file_name(filename.into())
+            .mime_str(media_type)?;
+        let form = multipart::Form::new().part("file", part);
+        let request = self.request(Method::POST, "/v1/files", &[FILES_API_BETA])?;
+        let response = self.send(request.multipart(form)).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Lists uploaded files, most recently created first. Pages are fetched as the stream is
+    /// consumed.
+    pub fn list_files(&self, params: ListParams) -> Paginated<FileMetadata> {
+        self.paginate("/v1/files", params, &[FILES_API_BETA])
+    }
+
+    /// Retrieves the metadata for a file.
+    pub async fn get_file(&self, file_id: &str) -> Result<FileMetadata> {
+        let path = format!("/v1/files/{file_id}");
+        let response = self
+            .send(self.request(Method::GET, &path, &[FILES_API_BETA])?)
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// Downloads the content of a file. Only files with `downloadable` set can be downloaded.
+    pub async fn download_file(&self, file_id: &str) -> Result<Vec<u8>> {
+        let path = format!("/v1/files/{file_id}/content");
+        let response = self
+            .send(self.request(Method::GET, &path, &[FILES_API_BETA])?)
+            .await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Deletes a file.
+    pub async fn delete_file(&self, file_id: &str) -> Result<DeletedFile> {
+        let path = format!("/v1/files/{file_id}");
+        let response = self
+            .send(self.request(Method::DELETE, &path, &[FILES_API_BETA])?)
+            .await?;
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, header, header_regex, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        tests::{test_client, text_response},
+        Content, MessagesRequest,
+    };
+
+    #[tokio::test]
+    async fn test_files() {
+        let server = MockServer::start().await;
+        let metadata = json!({
+            "id": "file_01",
+            "type": "file",
+            "filename": "chart.png",
+            "mime_type": "image/png",
+            "size_bytes": 4,
+            "created_at": "2025-06-01T00:00:00Z"
+        });
+        Mock::given(method("POST"))
+            .and(path("/v1/files"))
+            .and(header("anthropic-beta", FILES_API_BETA))
+            .and(header_regex(
+                "content-type",
+                "^multipart/form-data; boundary=",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&metadata))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(header("anthropic-beta", FILES_API_BETA))
+            .and(body_json(json!({
+                "model": crate::DEFAULT_MODEL,
+                "max_tokens": 1024,
+                "messages": [{"role": "user", "content": [
+                    {"type": "image", "source": {"type": "file", "file_id": "file_01"}}
+                ]}],
+                "stream": false
+            })))
+            .respond_with(text_response("A chart"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = test_client(&server);
+        let file = client
+            .upload_file_bytes("chart.png", "image/png", b"\x89PNG".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(file.id, "file_01");
+        assert!(!file.downloadable);
+
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::image_file(&file.id));
+        assert_eq!(request.betas(), [FILES_API_BETA]);
+        let response = client.messages(&request).await.unwrap();
+        assert_eq!(response.format_content(), "A chart");
+    }
+}
@@ -1,10 +1,10 @@
 //! Rust client for the Anthropic API.
-use std::{env, fs, path::Path};
+use std::{collections::HashMap, env, fmt, fs, path::Path, time::Duration};
 
 use base64::prelude::*;
 use futures_util::StreamExt;
-use log::trace;
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use log::{debug, trace};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
 use reqwest_eventsource::{Event, EventSource};
 use schemars::{schema_for, JsonSchema, Schema};
 use serde::{Deserialize, Serialize};
@@ -24,6 +24,13 @@ pub const ANTHROPIC_API_VERSION: &str = "2023-06-01";
 
 const DEFAULT_API_DOMAIN: &str = "api.anthropic.com";
 
+const ANTHROPIC_BETA_HEADER: &str = "anthropic-beta";
+
+const DEFAULT_USER_AGENT: &str = concat!("misanthropy/", env!("CARGO_PKG_VERSION"));
+
+/// Beta header value for the Files API, and for referencing uploaded files in messages.
+pub const FILES_API_BETA: &str = "files-api-2025-04-14";
+
 /// Name of the built-in text editor tool for Claude 4
 pub const TEXT_EDITOR_4: &str = "text_editor_20250429";
 
@@ -39,7 +46,60 @@ pub const TEXT_EDITOR_NAME_3: &str = "str_replace_editor";
 /// Name of the built-in text editor tool for Claude 4.x
 pub const TEXT_EDITOR_NAME_4: &str = "str_replace_based_edit_tool";
 
+/// Name of the built-in bash tool for Claude 4 and Claude 3.7
+pub const BASH_37: &str = "bash_20250124";
+
+/// Name of the built-in bash tool for Claude 3.5
+pub const BASH_35: &str = "bash_20241022";
+
+/// Name of the built-in bash tool
+pub const BASH_NAME: &str = "bash";
+
+/// Name of the built-in computer use tool for Claude Opus 4.5, which adds the zoom action
+pub const COMPUTER_45: &str = "computer_20251124";
+
+/// Name of the built-in computer use tool for Claude 4 and Claude 3.7
+pub const COMPUTER_37: &str = "computer_20250124";
+
+/// Name of the built-in computer use tool for Claude 3.5
+pub const COMPUTER_35: &str = "computer_20241022";
+
+/// Name of the built-in computer use tool
+pub const COMPUTER_NAME: &str = "computer";
+
+/// Type of the web search server tool
+pub const WEB_SEARCH: &str = "web_search_20250305";
+
+/// Name of the web search server tool
+pub const WEB_SEARCH_NAME: &str = "web_search";
+
+/// Type of the web fetch server tool
+pub const WEB_FETCH: &str = "web_fetch_20250910";
+
+/// Name of the web fetch server tool
+pub const WEB_FETCH_NAME: &str = "web_fetch";
+
+/// Type of the code execution server tool, which runs bash commands and edits files
+pub const CODE_EXECUTION: &str = "code_execution_20250825";
+
+/// Type of the original code execution server tool, which only runs Python
+pub const CODE_EXECUTION_PYTHON: &str = "code_execution_20250522";
+
+/// Name of the code execution server tool
+pub const CODE_EXECUTION_NAME: &str = "code_execution";
+
+mod agent;
+mod batches;
+mod citations;
 mod error;
+mod extract;
+mod files;
+mod mcp;
+mod models;
+mod pagination;
+mod registry;
+mod retry;
+mod server_tools;
 pub mod tools;
 
 /// Represents cache control options for conversation blocks.
@@ -58,27 +118,96 @@ impl CacheControl {
     }
 }
 
+pub use agent::*;
+pub use batches::*;
+pub use citations::*;
 pub use error::*;
+pub use files::*;
+pub use mcp::*;
+pub use models::*;
+pub use pagination::*;
+pub use registry::*;
+pub use retry::RetryPolicy;
+pub use server_tools::*;
 
 /// Specifies how the AI model should choose and use tools in a conversation.
 /// Can be set to automatic, any tool, or a specific tool.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+///
+/// Setting `disable_parallel_tool_use` limits the model to at most one tool use per response
+/// (exactly one for `Any` and `Tool`).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ToolChoice {
     /// Let the model automatically decide whether to use tools.
-    #[default]
-    Auto,
+    Auto {
+        #[serde(default, skip_serializing_if = "is_false")]
+        disable_parallel_tool_use: bool,
+    },
     /// Allow the model to use any available tool.
-    Any,
+    Any {
+        #[serde(default, skip_serializing_if = "is_false")]
+        disable_parallel_tool_use: bool,
+    },
     /// Instruct the model to use a specific tool.
     Tool {
         /// The name of the specific tool to use.
         name: String,
+        #[serde(default, skip_serializing_if = "is_false")]
+        disable_parallel_tool_use: bool,
     },
     /// Instruct the model not to use any tools.
     None,
 }
 
+impl Default for ToolChoice {
+    fn default() -> Self {
+        Self::auto()
+    }
+}
+
+impl ToolChoice {
+    /// Let the model automatically decide whether to use tools.
+    pub fn auto() -> Self {
+        Self::Auto {
+            disable_parallel_tool_use: false,
+        }
+    }
+
+    /// Allow the model to use any available tool.
+    pub fn any() -> Self {
+        Self::Any {
+            disable_parallel_tool_use: false,
+        }
+    }
+
+    /// Instruct the model to use a specific tool.
+    pub fn tool(name: impl Into<String>) -> Self {
+        Self::Tool {
+            name: name.into(),
+            disable_parallel_tool_use: false,
+        }
+    }
+
+    /// Sets whether the model may use more than one tool in a response. Has no effect on
+    /// `ToolChoice::None`.
+    pub fn with_disable_parallel_tool_use(mut self, disable: bool) -> Self {
+        match &mut self {
+            Self::Auto {
+                disable_parallel_tool_use,
+            }
+            | Self::Any {
+                disable_parallel_tool_use,
+            }
+            | Self::Tool {
+                disable_parallel_tool_use,
+                ..
+            } => *disable_parallel_tool_use = disable,
+            Self::None => {}
+        }
+        self
+    }
+}
+
 /// Represents a tool that can be used by the AI model in a conversation.
 ///
 /// This enum allows for different types of tools to be defined:
@@ -132,16 +261,128 @@ pub enum Tool {
 
         /// The type of the text editor tool. This must match the model, and should be either
         /// equal to the constants in `TEXT_EDITOR_35`, `TEXT_EDITOR_37`, `TEXT_EDITOR_4`.
-        #[serde(rename = "type")]
+        #[serde(rename = "type", deserialize_with = "text_editor_type")]
+        typ: String,
+
+        /// Optional cache control settings for the tool.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    Bash {
+        /// Must be equal to `BASH_NAME`.
+        name: String,
+
+        /// The type of the bash tool. This must match the model, and should be either
+        /// `BASH_35` or `BASH_37`.
+        #[serde(rename = "type", deserialize_with = "bash_type")]
+        typ: String,
+
+        /// Optional cache control settings for the tool.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    Computer {
+        /// Must be equal to `COMPUTER_NAME`.
+        name: String,
+
+        /// The type of the computer use tool. This must match the model, and should be one of
+        /// `COMPUTER_35`, `COMPUTER_37` or `COMPUTER_45`.
+        #[serde(rename = "type", deserialize_with = "computer_type")]
         typ: String,
 
+        /// The width of the display in pixels.
+        display_width_px: u32,
+
+        /// The height of the display in pixels.
+        display_height_px: u32,
+
+        /// The X11 display number, if any.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        display_number: Option<u32>,
+
+        /// Enables the zoom action. Only supported by `COMPUTER_45`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        enable_zoom: Option<bool>,
+
         /// Optional cache control settings for the tool.
         #[serde(skip_serializing_if = "Option::is_none")]
         cache_control: Option<CacheControl>,
     },
+    /// Web search, run by Anthropic.
+    WebSearch(WebSearchTool),
+    /// Web fetch, run by Anthropic.
+    WebFetch(WebFetchTool),
+    /// Code execution in a sandbox, run by Anthropic.
+    CodeExecution(CodeExecutionTool),
+}
+
+/// Deserializes a built-in tool type, checking its prefix so that untagged `Tool` variants
+/// with the same fields can be told apart.
+fn tool_type<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+    prefix: &str,
+) -> std::result::Result<String, D::Error> {
+    let typ = String::deserialize(deserializer)?;
+    if typ.starts_with(prefix) {
+        Ok(typ)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "expected a {prefix} tool type"
+        )))
+    }
+}
+
+fn text_editor_type<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    tool_type(deserializer, "text_editor_")
+}
+
+fn bash_type<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    tool_type(deserializer, "bash_")
+}
+
+fn computer_type<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    tool_type(deserializer, "computer_")
 }
 
 impl Tool {
+    /// The name of the tool.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Custom { name, .. }
+            | Self::TextEditor { name, .. }
+            | Self::Bash { name, .. }
+            | Self::Computer { name, .. } => name,
+            Self::WebSearch(tool) => &tool.name,
+            Self::WebFetch(tool) => &tool.name,
+            Self::CodeExecution(tool) => &tool.name,
+        }
+    }
+
+    /// The beta feature this tool requires, if any.
+    fn beta(&self) -> Option<&'static str> {
+        match self {
+            Self::Computer { typ, .. } => match typ.as_str() {
+                COMPUTER_35 => Some("computer-use-2024-10-22"),
+                COMPUTER_37 => Some("computer-use-2025-01-24"),
+                COMPUTER_45 => Some("computer-use-2025-11-24"),
+                _ => None,
+            },
+            Self::WebFetch(tool) if tool.typ == WEB_FETCH => Some("web-fetch-2025-09-10"),
+            Self::CodeExecution(tool) => match tool.typ.as_str() {
+                CODE_EXECUTION_PYTHON => Some("code-execution-2025-05-22"),
+                CODE_EXECUTION => Some("code-execution-2025-08-25"),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     /// Creates a new custom tool from a type implementing JsonSchema.
     ///
     /// The tool's name and description are automatically derived from the input type.
@@ -204,6 +445,9 @@ pub enum StreamEvent {
     },
     /// Indicates the completion of the entire message.
     MessageStop,
+    /// An event type this version of the library doesn't know about, kept as raw JSON.
+    #[serde(untagged)]
+    Unknown(Value),
 }
 
 /// An error that has occurred as part of a stream.
@@ -236,15 +480,31 @@ pub enum ContentBlockDelta {
         /// The thinking text to be appended.
         thinking: String,
     },
+    /// The signature of a thinking content block, sent just before the block stops.
+    SignatureDelta {
+        /// The signature verifying the thinking content.
+        signature: String,
+    },
+    /// A citation to be added to a text content block.
+    CitationsDelta {
+        /// The citation to be appended.
+        citation: Citation,
+    },
+    /// A delta type this version of the library doesn't know about, kept as raw JSON.
+    #[serde(untagged)]
+    Unknown(Value),
 }
 
 impl ContentBlockDelta {
     /// Returns a string representation of the delta type.
-    pub fn typ(&self) -> &'static str {
+    pub fn typ(&self) -> &str {
         match self {
             Self::TextDelta { .. } => "text_delta",
             Self::InputJsonDelta { .. } => "input_json_delta",
             Self::ThinkingDelta { .. } => "thinking_delta",
+            Self::SignatureDelta { .. } => "signature_delta",
+            Self::CitationsDelta { .. } => "citations_delta",
+            Self::Unknown(value) => unknown_type(value),
         }
     }
 }
@@ -272,6 +532,38 @@ pub struct StreamedResponse {
     pub response: MessagesResponse,
     /// The underlying event source for the stream, if active.
     event_source: Option<EventSource>,
+    /// Partial JSON input for tool use blocks, keyed by content block index. Fragments are
+    /// accumulated until the block stops, at which point they are parsed into the tool input.
+    partial_json: HashMap<usize, String>,
+    /// State for retrying the request, cleared once the first event is received.
+    retry: Option<StreamRetry>,
+}
+
+/// The original request and retry state for a stream.
+struct StreamRetry {
+    request: reqwest::RequestBuilder,
+    policy: RetryPolicy,
+    attempt: u32,
+}
+
+/// Converts an event source error into an error, reading the body of unsuccessful API responses.
+async fn stream_error(error: reqwest_eventsource::Error) -> Error {
+    match error {
+        reqwest_eventsource::Error::InvalidStatusCode(_, response) => {
+            Error::from_response(response).await
+        }
+        reqwest_eventsource::Error::Transport(e) => e.into(),
+        e => Error::StreamError(e.to_string()),
+    }
+}
+
+/// Creates an event source for a streaming request. Retries are handled by `StreamedResponse`
+/// according to the client's `RetryPolicy`, so the event source itself never reconnects.
+fn event_source(request: reqwest::RequestBuilder) -> Result<EventSource> {
+    let mut event_source =
+        EventSource::new(request).map_err(|e| Error::EventSourceError(e.to_string()))?;
+    event_source.set_retry_policy(Box::new(reqwest_eventsource::retry::Never));
+    Ok(event_source)
 }
 
 impl StreamedResponse {
@@ -279,6 +571,8 @@ impl StreamedResponse {
         Self {
             response: MessagesResponse::default(),
             event_source: Some(event_source),
+            partial_json: HashMap::new(),
+            retry: None,
         }
     }
 
@@ -299,38 +593,46 @@ impl StreamedResponse {
     /// After receiving a `MessageStop` event or when the stream is otherwise completed,
     /// this method will drop the internal `EventSource` and return `None` on subsequent calls.
     pub async fn next(&mut self) -> Option<Result<StreamEvent>> {
-        let event_source = self.event_source.as_mut()?;
-
-        while let Some(event) = event_source.next().await {
+        loop {
+            let event = match self.event_source.as_mut()?.next().await {
+                Some(event) => event,
+                None => break,
+            };
             match event {
                 Ok(Event::Open) => continue,
-                Ok(Event::Message(message)) => match serde_json::from_str(&message.data) {
-                    Ok(stream_event) => {
-                        trace!("stream event: {stream_event:#?}");
-                        self.merge_event(&stream_event);
-
-                        if matches!(stream_event, StreamEvent::MessageStop) {
-                            // Drop the event_source when we receive MessageStop
-                            self.event_source = None;
+                Ok(Event::Message(message)) => {
+                    // Once an event has been received the request can no longer be retried.
+                    self.retry = None;
+                    match serde_json::from_str(&message.data) {
+                        Ok(stream_event) => {
+                            trace!("stream event: {stream_event:#?}");
+                            if let Err(e) = self.merge_event(&stream_event) {
+                                return Some(Err(e));
+                            }
+
+                            if matches!(stream_event, StreamEvent::MessageStop) {
+                                // Drop the event_source when we receive MessageStop
+                                self.event_source = None;
+                            }
+
+                            return Some(Ok(stream_event));
                         }
 
-                        return Some(Ok(stream_event));
+                        Err(e) => return Some(Err(Error::ResponseParseError(e))),
                     }
-
-                    Err(e) => return Some(Err(Error::ResponseParseError(e))),
-                },
+                }
                 Err(e) => {
-                    // Check if this is a transport error that might have status code info
-                    if let reqwest_eventsource::Error::Transport(transport_err) = &e {
-                        if let Some(status) = transport_err.status() {
-                            if status.as_u16() == 429 {
-                                return Some(Err(Error::RateLimitExceeded(format!(
-                                    "Rate limit exceeded: {e}"
-                                ))));
-                            }
+                    let error = stream_error(e).await;
+                    if let Some(delay) = self.retry_delay(&error) {
+                        debug!("retrying stream in {delay:?} after error: {error}");
+                        tokio::time::sleep(delay).await;
+                        match self.reconnect() {
+                            Ok(()) => continue,
+                            Err(e) => return Some(Err(e)),
                         }
                     }
-                    return Some(Err(Error::StreamError(e.to_string())));
+                    self.event_source = None;
+                    return Some(Err(error));
                 }
             }
         }
@@ -340,7 +642,31 @@ impl StreamedResponse {
         None
     }
 
-    fn merge_event(&mut self, event: &StreamEvent) {
+    /// Returns the delay before retrying the stream after an error, or `None` if the error
+    /// should be returned to the caller.
+    fn retry_delay(&mut self, error: &Error) -> Option<Duration> {
+        let retry = self.retry.as_mut()?;
+        if retry.attempt >= retry.policy.max_retries || !retry.policy.retries(error) {
+            return None;
+        }
+        let delay = retry.policy.delay(retry.attempt, error.retry_after());
+        retry.attempt += 1;
+        Some(delay)
+    }
+
+    /// Re-sends the original request after a failure.
+    fn reconnect(&mut self) -> Result<()> {
+        if let Some(retry) = &self.retry {
+            let request = retry
+                .request
+                .try_clone()
+                .ok_or_else(|| Error::EventSourceError("request cannot be retried".into()))?;
+            self.event_source = Some(event_source(request)?);
+        }
+        Ok(())
+    }
+
+    fn merge_event(&mut self, event: &StreamEvent) -> Result<()> {
         match event {
             StreamEvent::MessageStart { message } => {
                 self.response.id = message.id.clone();
@@ -368,6 +694,11 @@ impl StreamedResponse {
                         ) => {
                             text.text.push_str(delta_text);
                         }
+                        (Content::Text(text), ContentBlockDelta::CitationsDelta { citation }) => {
+                            text.citations
+                                .get_or_insert_with(Vec::new)
+                                .push(citation.clone());
+                        }
                         (
                             Content::Thinking(thinking_content),
                             ContentBlockDelta::ThinkingDelta {
@@ -376,10 +707,25 @@ impl StreamedResponse {
                         ) => {
                             thinking_content.thinking.push_str(delta_thinking);
                         }
-                        (Content::ToolUse(_), _) => {
-                            unimplemented!(
-                                "Partial updates for ToolUse blocks are not supported yet"
-                            );
+                        (
+                            Content::Thinking(thinking_content),
+                            ContentBlockDelta::SignatureDelta { signature },
+                        ) => {
+                            thinking_content
+                                .signature
+                                .get_or_insert_with(String::new)
+                                .push_str(signature);
+                        }
+                        (
+                            Content::ToolUse(_)
+                            | Content::ServerToolUse(_)
+                            | Content::McpToolUse(_),
+                            ContentBlockDelta::InputJsonDelta { partial_json },
+                        ) => {
+                            self.partial_json
+                                .entry(*index)
+                                .or_default()
+                                .push_str(partial_json);
                         }
                         (block, delta) => {
                             log::warn!(
@@ -396,11 +742,35 @@ impl StreamedResponse {
                 self.response.stop_sequence = delta.stop_sequence.clone();
                 self.response.usage = self.response.usage.merge(usage);
             }
-            StreamEvent::ContentBlockStop { .. }
-            | StreamEvent::Ping
+            StreamEvent::ContentBlockStop { index } => {
+                if let Some(json) = self.partial_json.remove(index) {
+                    let input = match self.response.content.get_mut(*index) {
+                        Some(Content::ToolUse(tool_use) | Content::ServerToolUse(tool_use)) => {
+                            Some(&mut tool_use.input)
+                        }
+                        Some(Content::McpToolUse(tool_use)) => Some(&mut tool_use.input),
+                        _ => None,
+                    };
+                    if let Some(input) = input {
+                        // A tool with no parameters streams an empty input, in which case we keep
+                        // the initial value from the ContentBlockStart event.
+                        if !json.trim().is_empty() {
+                            *input = serde_json::from_str(&json).map_err(|source| {
+                                Error::ToolInputParseError {
+                                    index: *index,
+                                    source,
+                                }
+                            })?;
+                        }
+                    }
+                }
+            }
+            StreamEvent::Ping
             | StreamEvent::MessageStop
-            | StreamEvent::Error { .. } => {}
+            | StreamEvent::Error { .. }
+            | StreamEvent::Unknown(_) => {}
         }
+        Ok(())
     }
 
     pub fn content_text(&self) -> String {
@@ -431,6 +801,8 @@ pub enum ApiErrorType {
     PermissionError,
     /// The requested resource was not found (HTTP 404).
     NotFoundError,
+    /// The request exceeds the maximum allowed number of bytes (HTTP 413).
+    RequestTooLarge,
     /// The account has hit a rate limit (HTTP 429).
     RateLimitError,
     /// An unexpected error has occurred internal to Anthropic's systems (HTTP 500).
@@ -442,6 +814,37 @@ pub enum ApiErrorType {
     Other,
 }
 
+impl ApiErrorType {
+    /// The error type corresponding to an HTTP status code.
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            400 => Self::InvalidRequestError,
+            401 => Self::AuthenticationError,
+            403 => Self::PermissionError,
+            404 => Self::NotFoundError,
+            413 => Self::RequestTooLarge,
+            429 => Self::RateLimitError,
+            529 => Self::OverloadedError,
+            500..=599 => Self::ApiError,
+            _ => Self::Other,
+        }
+    }
+
+    /// The HTTP status code the API uses for this error type.
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::InvalidRequestError => 400,
+            Self::AuthenticationError => 401,
+            Self::PermissionError => 403,
+            Self::NotFoundError => 404,
+            Self::RequestTooLarge => 413,
+            Self::RateLimitError => 429,
+            Self::ApiError | Self::Other => 500,
+            Self::OverloadedError => 529,
+        }
+    }
+}
+
 /// The top-level error structure returned by the Anthropic API.
 /// Includes a nested ApiError with more details.
 #[derive(Debug, Deserialize)]
@@ -492,6 +895,20 @@ pub enum StopReason {
 
     /// The model invoked one or more tools, which terminated its generation.
     ToolUse,
+
+    /// The model declined to respond for safety reasons.
+    Refusal,
+
+    /// A long-running turn, such as one using server tools, was paused. Send the response back
+    /// as an assistant message to let the model continue.
+    PauseTurn,
+
+    /// The generation was stopped because it reached the model's context window limit.
+    ModelContextWindowExceeded,
+
+    /// A stop reason this version of the library doesn't know about.
+    #[serde(untagged)]
+    Unknown(String),
 }
 
 /// The response from the Anthropic API for a message request. Contains generated content, message
@@ -519,6 +936,14 @@ pub struct MessagesResponse {
 }
 
 impl MessagesResponse {
+    /// The tools the model asked to use in this response.
+    pub fn tool_uses(&self) -> impl Iterator<Item = &ToolUse> {
+        self.content.iter().filter_map(|content| match content {
+            Content::ToolUse(tool_use) => Some(tool_use),
+            _ => None,
+        })
+    }
+
     pub fn format_content(&self) -> String {
         let mut output = String::new();
         let mut has_user_messages = false;
@@ -535,17 +960,41 @@ impl MessagesResponse {
                 }
                 Content::Image(image) => {
                     output.push_str(&format!(
-                        "{}: [Image: {} {}]\n",
+                        "{}: [Image: {}]\n",
                         if self.role == Role::User {
                             "user"
                         } else {
                             "assistant"
                         },
-                        image.source.source_type,
-                        image.source.media_type
+                        image.source
                     ));
                     has_user_messages = true; // Always show roles if there are images
                 }
+                Content::Document(document) => {
+                    output.push_str(&format!(
+                        "{}: [Document: {}]\n",
+                        if self.role == Role::User {
+                            "user"
+                        } else {
+                            "assistant"
+                        },
+                        document
+                    ));
+                    has_user_messages = true; // Always show roles if there are documents
+                }
+                Content::SearchResult(result) => {
+                    output.push_str(&format!(
+                        "{}: [Search Result: {} ({})]\n",
+                        if self.role == Role::User {
+                            "user"
+                        } else {
+                            "assistant"
+                        },
+                        result.title,
+                        result.source
+                    ));
+                    has_user_messages = true; // Always show roles if there are search results
+                }
                 Content::ToolUse(tool_use) => {
                     output.push_str(&format!(
                         "{}: [Tool({}): {} {}]\n",
@@ -584,6 +1033,35 @@ impl MessagesResponse {
                         thinking.thinking
                     ));
                 }
+                Content::RedactedThinking(_) => {
+                    output.push_str(&format!(
+                        "{}: [Redacted Thinking]\n",
+                        if self.role == Role::User {
+                            "user"
+                        } else {
+                            "assistant"
+                        }
+                    ));
+                }
+                Content::ServerToolUse(_)
+                | Content::WebSearchToolResult(_)
+                | Content::WebFetchToolResult(_)
+                | Content::CodeExecutionToolResult(_)
+                | Content::BashCodeExecutionToolResult(_)
+                | Content::TextEditorCodeExecutionToolResult(_)
+                | Content::McpToolUse(_)
+                | Content::McpToolResult(_)
+                | Content::Unknown(_) => {
+                    output.push_str(&format!(
+                        "{}: {}\n",
+                        if self.role == Role::User {
+                            "user"
+                        } else {
+                            "assistant"
+                        },
+                        format_block(content)
+                    ));
+                }
             }
         }
 
@@ -633,26 +1111,108 @@ pub struct ToolResult {
     /// The unique identifier of the tool use instance.
     pub tool_use_id: String,
     /// The output of the tool. Arbitrary format, but should be intelligible to the assistant.
-    pub content: String,
+    pub content: ToolResultContent,
     /// Is the response an error?
-    #[serde(skip_serializing_if = "is_false")]
+    #[serde(default, skip_serializing_if = "is_false")]
     pub is_error: bool,
 }
 
 impl ToolResult {
-    pub fn new(tool_use_id: String, content: String) -> Self {
+    pub fn new(tool_use_id: String, content: impl Into<ToolResultContent>) -> Self {
         Self {
             tool_use_id,
-            content,
+            content: content.into(),
             is_error: false,
         }
     }
 }
 
+/// The output of a tool, either a string or a list of text, image, document and search result
+/// blocks.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ToolResultContent {
+    Text(String),
+    Blocks(Vec<Content>),
+}
+
+impl From<String> for ToolResultContent {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for ToolResultContent {
+    fn from(text: &str) -> Self {
+        Self::Text(text.into())
+    }
+}
+
+impl From<Vec<Content>> for ToolResultContent {
+    fn from(blocks: Vec<Content>) -> Self {
+        Self::Blocks(blocks)
+    }
+}
+
+impl fmt::Display for ToolResultContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(text) => write!(f, "{text}"),
+            Self::Blocks(blocks) => {
+                let blocks: Vec<String> = blocks.iter().map(format_block).collect();
+                write!(f, "{}", blocks.join("\n"))
+            }
+        }
+    }
+}
+
+/// A search result supplied by the application, e.g. from a retrieval tool, that the AI can
+/// cite like a document.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchResult {
+    /// Where the result came from, e.g. a URL or document id.
+    pub source: String,
+    /// The title of the result.
+    pub title: String,
+    /// The text blocks of the result.
+    pub content: Vec<Content>,
+    /// Whether the model may cite passages from this result.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citations: Option<CitationsConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+impl SearchResult {
+    /// Creates a search result with one text block per passage.
+    pub fn new<T: Into<String>>(
+        source: impl Into<String>,
+        title: impl Into<String>,
+        passages: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Self {
+            source: source.into(),
+            title: title.into(),
+            content: passages.into_iter().map(Content::text).collect(),
+            citations: None,
+            cache_control: None,
+        }
+    }
+
+    /// Enables or disables citations for the search result.
+    pub fn with_citations(mut self, enabled: bool) -> Self {
+        self.citations = Some(CitationsConfig { enabled });
+        self
+    }
+}
+
 /// Textual content in a message.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Text {
     pub text: String,
+    /// Passages of documents or search results supporting this text, if citations are enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citations: Option<Vec<Citation>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cache_control: Option<CacheControl>,
 }
@@ -661,6 +1221,7 @@ impl Text {
     pub fn new(text: impl Into<String>) -> Self {
         Self {
             text: text.into(),
+            citations: None,
             cache_control: None,
         }
     }
@@ -683,6 +1244,14 @@ impl ThinkingContent {
     }
 }
 
+/// Thinking content that was flagged by safety systems and encrypted. It must be passed back
+/// unmodified in the next request to continue the conversation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedactedThinking {
+    /// The encrypted thinking content.
+    pub data: String,
+}
+
 /// An image with its source information in a message.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Image {
@@ -698,86 +1267,359 @@ impl Image {
             cache_control: None,
         }
     }
+
+    /// Creates an image from raw image data, encoding it as Base64.
+    pub fn base64(media_type: impl Into<String>, data: impl AsRef<[u8]>) -> Self {
+        Self::new(Source::Base64 {
+            media_type: media_type.into(),
+            data: BASE64_STANDARD.encode(data),
+        })
+    }
 }
 
-/// A piece of content in a message.
+/// A document, such as a PDF or plain text, for the AI to read.
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(tag = "type", rename_all = "snake_case")]
-pub enum Content {
-    /// Textual content.
-    Text(Text),
-    /// An image with its source information.
-    Image(Image),
-    /// Details of a tool used by the AI.
-    ToolUse(ToolUse),
-    /// The result to a tool used by the AI.
-    ToolResult(ToolResult),
-    /// Thinking content from the AI.
-    Thinking(ThinkingContent),
+pub struct Document {
+    pub source: DocumentSource,
+    /// Optional title of the document, passed to the model but not used for citations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Optional context about the document, passed to the model but not used for citations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// Whether the model may cite passages from this document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citations: Option<CitationsConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
 }
 
-impl Content {
-    /// Returns a string representation of the content type.
-    pub fn typ(&self) -> &'static str {
-        match self {
-            Self::Text(_) => "text",
-            Self::Image(_) => "image",
-            Self::ToolUse(_) => "tool_use",
-            Self::ToolResult(_) => "tool_result",
-            Self::Thinking(_) => "thinking",
+impl Document {
+    pub fn new(source: DocumentSource) -> Self {
+        Self {
+            source,
+            title: None,
+            context: None,
+            citations: None,
+            cache_control: None,
         }
     }
 
-    /// Creates a new text content block.
-    pub fn text(text: impl Into<String>) -> Self {
-        Content::Text(Text::new(text))
+    /// Sets the title of the document.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
     }
 
-    /// Creates a new image content block from a file path by reading the image
-    /// data and encoding it as Base64.
-    pub fn image(path: impl AsRef<Path>) -> std::io::Result<Self> {
-        let path = path.as_ref();
-        let image_data = fs::read(path)?;
-        let base64_image = BASE64_STANDARD.encode(image_data);
+    /// Sets additional context about the document.
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
 
-        Ok(Content::Image(Image::new(Source {
-            source_type: "base64".to_string(),
-            media_type: Self::detect_media_type(path),
-            data: base64_image,
+    /// Enables or disables citations for the document.
+    pub fn with_citations(mut self, enabled: bool) -> Self {
+        self.citations = Some(CitationsConfig { enabled });
+        self
+    }
+
+    /// Sets the cache control for the document.
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+}
+
+/// Citation settings for a document.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CitationsConfig {
+    pub enabled: bool,
+}
+
+/// The source of a document in a message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DocumentSource {
+    /// Document data included in the request, e.g. a PDF.
+    Base64 {
+        /// MIME type of the document (e.g., "application/pdf").
+        media_type: String,
+        /// The base64-encoded document data.
+        data: String,
+    },
+    /// A plain text document. Citations refer to character ranges.
+    Text {
+        /// MIME type of the text, always "text/plain".
+        media_type: String,
+        /// The text of the document.
+        data: String,
+    },
+    /// A document made of custom content blocks. Citations refer to whole blocks, which is
+    /// useful for pre-chunked text.
+    Content {
+        /// The text or image blocks that make up the document.
+        content: Vec<Content>,
+    },
+    /// A PDF fetched from a URL.
+    Url {
+        /// The URL of the document.
+        url: String,
+    },
+    /// A document uploaded with the Files API.
+    File {
+        /// The id of the uploaded file.
+        file_id: String,
+    },
+}
+
+impl fmt::Display for DocumentSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentSource::Base64 { media_type, .. } => write!(f, "base64 {media_type}"),
+            DocumentSource::Text { .. } => write!(f, "text"),
+            DocumentSource::Content { content } => write!(f, "{} content blocks", content.len()),
+            DocumentSource::Url { url } => write!(f, "url {url}"),
+            DocumentSource::File { file_id } => write!(f, "file {file_id}"),
+        }
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.title {
+            Some(title) => write!(f, "{title} ({})", self.source),
+            None => write!(f, "{}", self.source),
+        }
+    }
+}
+
+/// A piece of content in a message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Content {
+    /// Textual content.
+    Text(Text),
+    /// An image with its source information.
+    Image(Image),
+    /// A document, such as a PDF or plain text.
+    Document(Document),
+    /// A search result that can be cited.
+    SearchResult(SearchResult),
+    /// Details of a tool used by the AI.
+    ToolUse(ToolUse),
+    /// The result to a tool used by the AI.
+    ToolResult(ToolResult),
+    /// Thinking content from the AI.
+    Thinking(ThinkingContent),
+    /// Encrypted thinking content from the AI.
+    RedactedThinking(RedactedThinking),
+    /// A server tool, such as web search, used by the AI. Anthropic runs the tool and adds
+    /// its result to the response.
+    ServerToolUse(ToolUse),
+    /// The results of a web search server tool use.
+    WebSearchToolResult(WebSearchToolResult),
+    /// The result of a web fetch server tool use.
+    WebFetchToolResult(WebFetchToolResult),
+    /// The result of running Python code with `CODE_EXECUTION_PYTHON`.
+    CodeExecutionToolResult(CodeExecutionToolResult),
+    /// The result of running a bash command with `CODE_EXECUTION`.
+    BashCodeExecutionToolResult(BashCodeExecutionToolResult),
+    /// The result of a file operation with `CODE_EXECUTION`.
+    TextEditorCodeExecutionToolResult(TextEditorCodeExecutionToolResult),
+    /// A tool on an MCP server used by the AI through the MCP connector.
+    McpToolUse(McpToolUse),
+    /// The result of an MCP tool use.
+    McpToolResult(McpToolResult),
+    /// A content type this version of the library doesn't know about, kept as raw JSON so it
+    /// can be sent back unchanged.
+    #[serde(untagged)]
+    Unknown(Value),
+}
+
+impl Content {
+    /// Returns a string representation of the content type.
+    pub fn typ(&self) -> &str {
+        match self {
+            Self::Text(_) => "text",
+            Self::Image(_) => "image",
+            Self::Document(_) => "document",
+            Self::SearchResult(_) => "search_result",
+            Self::ToolUse(_) => "tool_use",
+            Self::ToolResult(_) => "tool_result",
+            Self::Thinking(_) => "thinking",
+            Self::RedactedThinking(_) => "redacted_thinking",
+            Self::ServerToolUse(_) => "server_tool_use",
+            Self::WebSearchToolResult(_) => "web_search_tool_result",
+            Self::WebFetchToolResult(_) => "web_fetch_tool_result",
+            Self::CodeExecutionToolResult(_) => "code_execution_tool_result",
+            Self::BashCodeExecutionToolResult(_) => "bash_code_execution_tool_result",
+            Self::TextEditorCodeExecutionToolResult(_) => "text_editor_code_execution_tool_result",
+            Self::McpToolUse(_) => "mcp_tool_use",
+            Self::McpToolResult(_) => "mcp_tool_result",
+            Self::Unknown(value) => unknown_type(value),
+        }
+    }
+
+    /// Creates a new text content block.
+    pub fn text(text: impl Into<String>) -> Self {
+        Content::Text(Text::new(text))
+    }
+
+    /// Creates a new image content block from a file path by reading the image
+    /// data and encoding it as Base64.
+    pub fn image(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let image_data = fs::read(path)?;
+        let base64_image = BASE64_STANDARD.encode(image_data);
+
+        Ok(Content::Image(Image::new(Source::Base64 {
+            media_type: detect_media_type(path),
+            data: base64_image,
         })))
     }
 
+    /// Creates a new image content block referencing an image by URL.
+    pub fn image_url(url: impl Into<String>) -> Self {
+        Content::Image(Image::new(Source::Url { url: url.into() }))
+    }
+
+    /// Creates a new image content block referencing an image uploaded with the Files API.
+    pub fn image_file(file_id: impl Into<String>) -> Self {
+        Content::Image(Image::new(Source::File {
+            file_id: file_id.into(),
+        }))
+    }
+
+    /// Creates a new document content block from a PDF file by reading the data and encoding
+    /// it as Base64.
+    pub fn pdf(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let data = fs::read(path)?;
+        Ok(Content::Document(Document::new(DocumentSource::Base64 {
+            media_type: "application/pdf".into(),
+            data: BASE64_STANDARD.encode(data),
+        })))
+    }
+
+    /// Creates a new document content block referencing a PDF by URL.
+    pub fn pdf_url(url: impl Into<String>) -> Self {
+        Content::Document(Document::new(DocumentSource::Url { url: url.into() }))
+    }
+
+    /// Creates a new plain text document content block.
+    pub fn text_document(text: impl Into<String>) -> Self {
+        Content::Document(Document::new(DocumentSource::Text {
+            media_type: "text/plain".into(),
+            data: text.into(),
+        }))
+    }
+
+    /// Creates a new document content block referencing a document uploaded with the Files API.
+    pub fn document_file(file_id: impl Into<String>) -> Self {
+        Content::Document(Document::new(DocumentSource::File {
+            file_id: file_id.into(),
+        }))
+    }
+
     /// Creates a tool result block given a tool use and some content.
     pub fn tool_result(tool_use: &ToolUse, content: impl Into<String>) -> Self {
+        Content::ToolResult(ToolResult::new(tool_use.id.clone(), content.into()))
+    }
+
+    /// Creates a tool result block with text, image, document or search result blocks.
+    pub fn tool_result_blocks(tool_use: &ToolUse, blocks: Vec<Content>) -> Self {
+        Content::ToolResult(ToolResult::new(tool_use.id.clone(), blocks))
+    }
+
+    /// Creates a tool result block reporting that the tool failed.
+    pub fn tool_error(tool_use: &ToolUse, message: impl Into<String>) -> Self {
         Content::ToolResult(ToolResult {
-            tool_use_id: tool_use.id.clone(),
-            content: content.into(),
-            is_error: false,
+            is_error: true,
+            ..ToolResult::new(tool_use.id.clone(), message.into())
         })
     }
 
-    fn detect_media_type(path: &Path) -> String {
-        match path.extension().and_then(std::ffi::OsStr::to_str) {
-            Some("png") => "image/png",
-            Some("jpg") | Some("jpeg") => "image/jpeg",
-            Some("gif") => "image/gif",
-            Some("webp") => "image/webp",
-            _ => "application/octet-stream", // Default to binary data if unknown
+    /// Adds any beta features this content block requires to `betas`.
+    fn add_betas(&self, betas: &mut Vec<&'static str>) {
+        let mut add = |beta| {
+            if !betas.contains(&beta) {
+                betas.push(beta);
+            }
+        };
+        match self {
+            Content::Image(Image {
+                source: Source::File { .. },
+                ..
+            })
+            | Content::Document(Document {
+                source: DocumentSource::File { .. },
+                ..
+            }) => add(FILES_API_BETA),
+            Content::ToolResult(ToolResult {
+                content: ToolResultContent::Blocks(blocks),
+                ..
+            }) => {
+                for block in blocks {
+                    block.add_betas(betas);
+                }
+            }
+            _ => {}
         }
-        .to_string()
     }
 }
 
-/// Metadata for an image in a message.
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Source {
-    /// The type of image source (e.g., "base64").
-    #[serde(rename = "type")]
-    pub source_type: String,
-    /// MIME type of the image (e.g., "image/jpeg").
-    pub media_type: String,
-    /// The image data, typically base64-encoded.
-    pub data: String,
+/// The `type` field of an unrecognized JSON object.
+fn unknown_type(value: &Value) -> &str {
+    value["type"].as_str().unwrap_or("unknown")
+}
+
+/// Guesses the media type of a file from its extension.
+pub(crate) fn detect_media_type(path: &Path) -> String {
+    match path.extension().and_then(std::ffi::OsStr::to_str) {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("pdf") => "application/pdf",
+        Some("txt") => "text/plain",
+        Some("md") => "text/markdown",
+        Some("csv") => "text/csv",
+        Some("json") => "application/json",
+        _ => "application/octet-stream", // Default to binary data if unknown
+    }
+    .to_string()
+}
+
+/// The source of an image in a message.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Source {
+    /// Image data included in the request.
+    Base64 {
+        /// MIME type of the image (e.g., "image/jpeg").
+        media_type: String,
+        /// The base64-encoded image data.
+        data: String,
+    },
+    /// An image fetched from a URL.
+    Url {
+        /// The URL of the image.
+        url: String,
+    },
+    /// An image uploaded with the Files API.
+    File {
+        /// The id of the uploaded file.
+        file_id: String,
+    },
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Base64 { media_type, .. } => write!(f, "base64 {media_type}"),
+            Source::Url { url } => write!(f, "url {url}"),
+            Source::File { file_id } => write!(f, "file {file_id}"),
+        }
+    }
 }
 
 /// Token usage statistics for a message.
@@ -811,7 +1653,7 @@ impl Usage {
 }
 
 fn is_default_tool_choice(choice: &ToolChoice) -> bool {
-    *choice == ToolChoice::Auto
+    *choice == ToolChoice::default()
 }
 
 fn is_false(b: &bool) -> bool {
@@ -891,6 +1733,9 @@ pub struct MessagesRequest {
     /// Recommended for advanced use cases only.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub top_p: Option<f32>,
+    /// Remote MCP servers whose tools the AI can use through the MCP connector.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mcp_servers: Vec<McpServer>,
 }
 
 impl Default for MessagesRequest {
@@ -909,6 +1754,7 @@ impl Default for MessagesRequest {
             metadata: None,
             top_k: None,
             top_p: None,
+            mcp_servers: Vec::new(),
         }
     }
 }
@@ -968,18 +1814,100 @@ impl MessagesRequest {
         self
     }
 
+    /// Adds a bash tool to the request.
+    ///
+    /// # Arguments
+    ///
+    /// * `typ` - The type of the bash tool. This must match the model, and should be either
+    ///   `BASH_35` or `BASH_37`.
+    pub fn with_bash(mut self, typ: impl Into<String>) -> Self {
+        self.tools.push(Tool::Bash {
+            name: BASH_NAME.into(),
+            typ: typ.into(),
+            cache_control: None,
+        });
+        self
+    }
+
+    /// Adds a computer use tool to the request. The required beta feature is enabled
+    /// automatically.
+    ///
+    /// # Arguments
+    ///
+    /// * `typ` - The type of the computer use tool. This must match the model, and should be one
+    ///   of `COMPUTER_35`, `COMPUTER_37` or `COMPUTER_45`.
+    /// * `width` - The width of the display in pixels.
+    /// * `height` - The height of the display in pixels.
+    pub fn with_computer(mut self, typ: impl Into<String>, width: u32, height: u32) -> Self {
+        self.tools.push(Tool::Computer {
+            name: COMPUTER_NAME.into(),
+            typ: typ.into(),
+            display_width_px: width,
+            display_height_px: height,
+            display_number: None,
+            enable_zoom: None,
+            cache_control: None,
+        });
+        self
+    }
+
+    /// Adds the web search server tool to the request.
+    pub fn with_web_search(mut self, tool: WebSearchTool) -> Self {
+        self.tools.push(Tool::WebSearch(tool));
+        self
+    }
+
+    /// Adds the web fetch server tool to the request. The required beta feature is enabled
+    /// automatically.
+    pub fn with_web_fetch(mut self, tool: WebFetchTool) -> Self {
+        self.tools.push(Tool::WebFetch(tool));
+        self
+    }
+
+    /// Adds the code execution server tool to the request. The required beta feature is enabled
+    /// automatically.
+    ///
+    /// # Arguments
+    ///
+    /// * `typ` - The type of the code execution tool, `CODE_EXECUTION` or
+    ///   `CODE_EXECUTION_PYTHON`.
+    pub fn with_code_execution(mut self, typ: impl Into<String>) -> Self {
+        self.tools
+            .push(Tool::CodeExecution(CodeExecutionTool::new(typ)));
+        self
+    }
+
+    /// Adds a remote MCP server whose tools the AI can use. The MCP connector beta feature is
+    /// enabled automatically.
+    pub fn with_mcp_server(mut self, server: McpServer) -> Self {
+        self.mcp_servers.push(server);
+        self
+    }
+
     /// Adds a custom tool to the request.
     pub fn with_tool(mut self, tool: Tool) -> Self {
         self.tools.push(tool);
         self
     }
 
+    /// Adds several tools to the request, e.g. from `ToolRegistry::tools`.
+    pub fn with_tools(mut self, tools: impl IntoIterator<Item = Tool>) -> Self {
+        self.tools.extend(tools);
+        self
+    }
+
     /// Sets the tool choice for the AI model.
     pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
         self.tool_choice = tool_choice;
         self
     }
 
+    /// Limits the model to at most one tool use per response, keeping the current tool choice.
+    pub fn with_disable_parallel_tool_use(mut self, disable: bool) -> Self {
+        self.tool_choice = self.tool_choice.with_disable_parallel_tool_use(disable);
+        self
+    }
+
     /// Sets the AI model to use for generating the response.
     pub fn with_model(mut self, model: impl Into<String>) -> Self {
         self.model = model.into();
@@ -1055,6 +1983,46 @@ impl MessagesRequest {
         self
     }
 
+    /// Beta features used by this request. These are enabled automatically with the
+    /// `anthropic-beta` header when the request is sent.
+    pub fn betas(&self) -> Vec<&'static str> {
+        let mut betas = Vec::new();
+        if !self.mcp_servers.is_empty() {
+            betas.push("mcp-client-2025-04-04");
+        }
+        for beta in self.tools.iter().filter_map(Tool::beta) {
+            if !betas.contains(&beta) {
+                betas.push(beta);
+            }
+        }
+        let messages = self.messages.iter().flat_map(|m| &m.content);
+        for content in self.system.iter().chain(messages) {
+            content.add_betas(&mut betas);
+        }
+        betas
+    }
+
+    /// The request body for the token counting endpoint. This is the messages request with the
+    /// fields that only affect generation removed, since the endpoint rejects them.
+    fn count_tokens_body(&self) -> Result<Value> {
+        const GENERATION_FIELDS: &[&str] = &[
+            "max_tokens",
+            "stream",
+            "temperature",
+            "top_k",
+            "top_p",
+            "stop_sequences",
+            "metadata",
+        ];
+        let mut body = serde_json::to_value(self)?;
+        if let Value::Object(fields) = &mut body {
+            for field in GENERATION_FIELDS {
+                fields.remove(*field);
+            }
+        }
+        Ok(body)
+    }
+
     /// Add a user message to the conversation history. Appends the content to the last user
     /// message if we're still in the same role, otherwise creates a new user message.
     pub fn add_user(&mut self, content: Content) {
@@ -1084,6 +2052,13 @@ impl MessagesRequest {
     }
 }
 
+/// The number of input tokens a request would use, as returned by `Anthropic::count_tokens`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TokenCount {
+    /// The total number of tokens across messages, system prompt, tools and thinking.
+    pub input_tokens: u32,
+}
+
 /// A single message in a conversation, with a role and content.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Message {
@@ -1107,34 +2082,7 @@ impl Message {
             Role::Assistant => "Assistant: ",
         };
 
-        let content_strings: Vec<String> = self
-            .content
-            .iter()
-            .map(|content| match content {
-                Content::Text(text) => text.text.clone(),
-                Content::Image(image) => {
-                    format!(
-                        "[Image: {} {}]",
-                        image.source.source_type, image.source.media_type
-                    )
-                }
-                Content::ToolUse(tool_use) => {
-                    format!(
-                        "[Tool({}): {} {}]",
-                        tool_use.id, tool_use.name, tool_use.input
-                    )
-                }
-                Content::ToolResult(tool_result) => {
-                    format!(
-                        "[Tool Result({}): {}]",
-                        tool_result.tool_use_id, tool_result.content
-                    )
-                }
-                Content::Thinking(thinking) => {
-                    format!("[Thinking: {}]", thinking.thinking)
-                }
-            })
-            .collect();
+        let content_strings: Vec<String> = self.content.iter().map(format_block).collect();
 
         let formatted_content = content_strings.join("\n");
 
@@ -1142,24 +2090,91 @@ impl Message {
     }
 }
 
+/// Formats a single content block for display.
+fn format_block(content: &Content) -> String {
+    match content {
+        Content::Text(text) => text.text.clone(),
+        Content::Image(image) => format!("[Image: {}]", image.source),
+        Content::Document(document) => format!("[Document: {document}]"),
+        Content::SearchResult(result) => {
+            format!("[Search Result: {} ({})]", result.title, result.source)
+        }
+        Content::ToolUse(tool_use) => {
+            format!(
+                "[Tool({}): {} {}]",
+                tool_use.id, tool_use.name, tool_use.input
+            )
+        }
+        Content::ToolResult(tool_result) => {
+            format!(
+                "[Tool {}({}): {}]",
+                if tool_result.is_error {
+                    "Error"
+                } else {
+                    "Result"
+                },
+                tool_result.tool_use_id,
+                tool_result.content
+            )
+        }
+        Content::Thinking(thinking) => {
+            format!("[Thinking: {}]", thinking.thinking)
+        }
+        Content::RedactedThinking(_) => "[Redacted Thinking]".to_string(),
+        Content::ServerToolUse(tool_use) => {
+            format!(
+                "[Server Tool({}): {} {}]",
+                tool_use.id, tool_use.name, tool_use.input
+            )
+        }
+        Content::WebSearchToolResult(result) => result.to_string(),
+        Content::WebFetchToolResult(result) => result.to_string(),
+        Content::CodeExecutionToolResult(result) => result.to_string(),
+        Content::BashCodeExecutionToolResult(result) => result.to_string(),
+        Content::TextEditorCodeExecutionToolResult(result) => result.to_string(),
+        Content::McpToolUse(tool_use) => tool_use.to_string(),
+        Content::McpToolResult(result) => result.to_string(),
+        Content::Unknown(value) => format!("[{}]", unknown_type(value)),
+    }
+}
+
 /// Client for interacting with the Anthropic API.
 /// Manages authentication and default parameters for requests.
-#[derive(Debug)]
+///
+/// The client is cheap to clone and can be shared between tasks. Clones share the same
+/// underlying HTTP connection pool.
+#[derive(Debug, Clone)]
 pub struct Anthropic {
     api_key: String,
     base_url: String,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    client: reqwest::Client,
 }
 
 impl Anthropic {
     /// Creates a new Anthropic client with an optional API key.
     /// Uses default values for model and max_tokens.
     pub fn new(api_key: &str) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static(DEFAULT_USER_AGENT));
         Self {
             api_key: api_key.to_string(),
             base_url: format!("https://{DEFAULT_API_DOMAIN}"),
+            headers,
+            timeout: None,
+            retry: RetryPolicy::default(),
+            client: reqwest::Client::new(),
         }
     }
 
+    /// Returns a builder for configuring a client with a custom base URL, headers, timeouts or
+    /// HTTP client.
+    pub fn builder() -> AnthropicBuilder {
+        AnthropicBuilder::default()
+    }
+
     /// Creates an Anthropic client using the API key from the environment.
     /// Reads the key from the ANTHROPIC_API_KEY environment variable.
     pub fn from_env() -> Result<Self> {
@@ -1177,60 +2192,316 @@ impl Anthropic {
         }
     }
 
-    /// Creates the headers for API requests, including the beta header if enabled.
-    fn create_headers(&self) -> Result<HeaderMap> {
+    /// The base URL requests are sent to.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Creates the headers for API requests, enabling the given beta features. Custom headers
+    /// configured on the client take precedence over the defaults, and betas are added to any
+    /// custom `anthropic-beta` header.
+    fn create_headers(&self, betas: &[&str]) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
         headers.insert("x-api-key", HeaderValue::from_str(&self.api_key)?);
         headers.insert(
             "anthropic-version",
             HeaderValue::from_static(ANTHROPIC_API_VERSION),
         );
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        for (name, value) in &self.headers {
+            headers.insert(name, value.clone());
+        }
+        if !betas.is_empty() {
+            let mut all: Vec<&str> = match headers.get(ANTHROPIC_BETA_HEADER) {
+                Some(value) => value.to_str().unwrap_or_default().split(',').collect(),
+                None => Vec::new(),
+            };
+            for beta in betas {
+                if !all.iter().any(|b| b.trim() == *beta) {
+                    all.push(beta);
+                }
+            }
+            let value = HeaderValue::from_str(&all.join(","))?;
+            headers.insert(ANTHROPIC_BETA_HEADER, value);
+        }
 
         Ok(headers)
     }
 
+    /// Starts a request to an API path, e.g. `/v1/messages`, with the given beta features
+    /// enabled.
+    fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        betas: &[&str],
+    ) -> Result<reqwest::RequestBuilder> {
+        Ok(self
+            .client
+            .request(method, format!("{}{path}", self.base_url))
+            .headers(self.create_headers(betas)?))
+    }
+
+    /// Starts a POST request to an API path.
+    fn post(&self, path: &str) -> Result<reqwest::RequestBuilder> {
+        self.request(reqwest::Method::POST, path, &[])
+    }
+
+    /// Starts a GET request to an API path.
+    fn get(&self, path: &str) -> Result<reqwest::RequestBuilder> {
+        self.request(reqwest::Method::GET, path, &[])
+    }
+
+    /// Starts a DELETE request to an API path.
+    fn delete(&self, path: &str) -> Result<reqwest::RequestBuilder> {
+        self.request(reqwest::Method::DELETE, path, &[])
+    }
+
+    /// Sends a request, retrying transient failures according to the client's `RetryPolicy`.
+    /// Returns an error if the final response does not have a success status.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let request = match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        };
+        self.send_without_timeout(request).await
+    }
+
+    /// Sends a request like `send`, but without the client's total timeout, for responses whose
+    /// body is read incrementally and may take a long time to download.
+    pub(crate) async fn send_without_timeout(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            let Some(current) = request.try_clone() else {
+                // Requests with streaming bodies can't be replayed, so they're sent only once.
+                let response = request.send().await?;
+                if response.status().is_success() {
+                    return Ok(response);
+                }
+                return Err(Error::from_response(response).await);
+            };
+            let error = match current.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => Error::from_response(response).await,
+                Err(e) => e.into(),
+            };
+            if attempt >= self.retry.max_retries || !self.retry.retries(&error) {
+                return Err(error);
+            }
+            let delay = self.retry.delay(attempt, error.retry_after());
+            debug!("retrying request in {delay:?} after error: {error}");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
     /// Sends a message request to the Anthropic API and returns a streaming response.
     /// Allows processing of incremental updates as they arrive from the API.
     ///
-    /// It is an error to pass a `MessagesRequest` with `stream` set to `false`.
+    /// It is an error to pass a `MessagesRequest` with `stream` set to `false`. The client's
+    /// request timeout does not apply to streams, since they may legitimately run for a long
+    /// time.
     pub fn messages_stream(&self, request: &MessagesRequest) -> Result<StreamedResponse> {
         if !request.stream {
-            return Err(Error::BadRequest(
+            return Err(Error::InvalidRequest(
                 "Streaming requests must have stream set to true".to_string(),
             ));
         }
-        let event_source = EventSource::new(
-            reqwest::Client::new()
-                .post(format!("{}/v1/messages", self.base_url))
-                .headers(self.create_headers()?)
-                .json(&request),
-        )
-        .map_err(|e| Error::EventSourceError(e.to_string()))?;
+        let request = self
+            .request(reqwest::Method::POST, "/v1/messages", &request.betas())?
+            .json(&request);
+        let mut response = StreamedResponse::new(event_source(
+            request.try_clone().expect("JSON requests can be cloned"),
+        )?);
+        response.retry = Some(StreamRetry {
+            request,
+            policy: self.retry.clone(),
+            attempt: 0,
+        });
 
-        Ok(StreamedResponse::new(event_source))
+        Ok(response)
     }
 
     /// Sends a message request to the Anthropic API and returns the response.
     /// Uses client defaults for model and max_tokens if not specified in the request.
     pub async fn messages(&self, request: &MessagesRequest) -> Result<MessagesResponse> {
-        let client = reqwest::Client::new();
-        let response = client
-            .post(format!("{}/v1/messages", self.base_url))
-            .headers(self.create_headers()?)
-            .json(&request)
-            .send()
-            .await?;
-
-        let status = response.status();
-
-        if status.is_success() {
-            let messages_response: MessagesResponse = response.json().await?;
-            Ok(messages_response)
-        } else {
-            let error_response: ApiErrorResponse = response.json().await?;
-            Err(error_response.into())
+        let builder = self.request(reqwest::Method::POST, "/v1/messages", &request.betas())?;
+        let response = self.send(builder.json(&request)).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Sends a message request, automatically continuing the turn whenever the model pauses
+    /// with `StopReason::PauseTurn`.
+    ///
+    /// Each paused response is added to the conversation as an assistant message and the
+    /// request is sent again, up to `max_continuations` times. The returned response combines
+    /// the content and usage of every part of the turn, and has the stop reason of the last
+    /// part.
+    pub async fn messages_with_continuation(
+        &self,
+        request: &MessagesRequest,
+        max_continuations: u32,
+    ) -> Result<MessagesResponse> {
+        let mut request = request.clone();
+        let turn = request.messages.len();
+        let mut combined = self.messages(&request).await?;
+        for _ in 0..max_continuations {
+            if combined.stop_reason != Some(StopReason::PauseTurn) {
+                break;
+            }
+            // The turn so far is sent back as an assistant message, which the model continues.
+            request.messages.truncate(turn);
+            request.merge_response(&combined);
+            let response = self.messages(&request).await?;
+
+            combined.id = response.id;
+            combined.content.extend(response.content);
+            combined.stop_reason = response.stop_reason;
+            combined.stop_sequence = response.stop_sequence;
+            combined.usage = combined.usage.merge(&response.usage);
         }
+        Ok(combined)
+    }
+
+    /// Counts the input tokens a message request would use, without creating a message. The
+    /// messages, system prompt, tools, tool choice and thinking configuration are counted;
+    /// generation settings such as `max_tokens` and `stream` are ignored.
+    pub async fn count_tokens(&self, request: &MessagesRequest) -> Result<TokenCount> {
+        let body = request.count_tokens_body()?;
+        let builder = self.request(
+            reqwest::Method::POST,
+            "/v1/messages/count_tokens",
+            &request.betas(),
+        )?;
+        let response = self.send(builder.json(&body)).await?;
+        Ok(response.json().await?)
+    }
+}
+
+/// Builder for an [`Anthropic`] client.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # fn main() -> misanthropy::Result<()> {
+/// let client = misanthropy::Anthropic::builder()
+///     .with_api_key("sk-...")
+///     .with_base_url("http://localhost:8080")
+///     .with_header("x-gateway-team", "search")
+///     .with_timeout(Duration::from_secs(120))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct AnthropicBuilder {
+    api_key: Option<String>,
+    base_url: Option<String>,
+    headers: Vec<(String, String)>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    retry: Option<RetryPolicy>,
+    client: Option<reqwest::Client>,
+}
+
+impl AnthropicBuilder {
+    /// Sets the API key. If not set, the key is read from the ANTHROPIC_API_KEY environment
+    /// variable when the client is built.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Sets the base URL for requests, e.g. `http://localhost:8080`. Defaults to
+    /// `https://api.anthropic.com`.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Adds a header that is sent with every request. Headers set here override the client's
+    /// defaults, including `anthropic-version`.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the timeout for establishing a connection. Ignored if a custom HTTP client is
+    /// supplied with `with_client`.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the total timeout for non-streaming requests, from connection to the end of the
+    /// response body. It doesn't apply to streams or to downloading batch results.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the user agent sent with every request.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Sets the policy for retrying transient failures. Use `RetryPolicy::none()` to disable
+    /// retries.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Uses an existing HTTP client, e.g. to share a connection pool or proxy configuration
+    /// with the rest of an application.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Builds the client.
+    pub fn build(self) -> Result<Anthropic> {
+        let api_key = match self.api_key {
+            Some(api_key) => api_key,
+            None => env::var(ANTHROPIC_API_KEY_ENV)?,
+        };
+        let base_url = self
+            .base_url
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| format!("https://{DEFAULT_API_DOMAIN}"));
+
+        let mut headers = HeaderMap::new();
+        let user_agent = self.user_agent.unwrap_or_else(|| DEFAULT_USER_AGENT.into());
+        headers.insert(USER_AGENT, HeaderValue::from_str(&user_agent)?);
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+
+        let client = match self.client {
+            Some(client) => client,
+            None => {
+                let mut builder = reqwest::Client::builder();
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                builder.build()?
+            }
+        };
+
+        Ok(Anthropic {
+            api_key,
+            base_url,
+            headers,
+            timeout: self.timeout,
+            retry: self.retry.unwrap_or_default(),
+            client,
+        })
     }
 }
 
@@ -1238,6 +2509,10 @@ impl Anthropic {
 mod tests {
     use super::*;
     use serde_json::Value;
+    use wiremock::{
+        matchers::{body_json, body_partial_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
 
     /// This is a test description
     #[allow(dead_code)]
@@ -1255,6 +2530,36 @@ mod tests {
         OptionB,
     }
 
+    #[test]
+    fn test_computer_tool() {
+        let request =
+            MessagesRequest::default()
+                .with_bash(BASH_37)
+                .with_computer(COMPUTER_37, 1280, 800);
+        assert_eq!(request.betas(), ["computer-use-2025-01-24"]);
+
+        let json = serde_json::to_value(&request.tools).unwrap();
+        assert_eq!(
+            json[1],
+            json!({
+                "name": "computer",
+                "type": "computer_20250124",
+                "display_width_px": 1280,
+                "display_height_px": 800
+            })
+        );
+        let tools: Vec<Tool> = serde_json::from_value(json).unwrap();
+        assert!(matches!(tools[0], Tool::Bash { .. }));
+        assert!(matches!(
+            tools[1],
+            Tool::Computer {
+                display_width_px: 1280,
+                display_number: None,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn test_tool_creation_and_serialization() {
         // Create a tool
@@ -1272,7 +2577,7 @@ mod tests {
             Tool::Custom {
                 name, description, ..
             } => (name, description),
-            Tool::TextEditor { name, .. } => (name, &"Text editor tool".to_string()),
+            _ => panic!("Expected Tool::Custom"),
         };
         assert_eq!(name, "testtool");
         assert_eq!(description, "This is a test description");
@@ -1321,6 +2626,297 @@ mod tests {
         assert!(enum_values.contains(&Value::String("OptionB".to_string())));
     }
 
+    #[test]
+    fn test_builder() {
+        fn assert_shareable<T: Clone + Send + Sync>() {}
+        assert_shareable::<Anthropic>();
+
+        let client = Anthropic::builder()
+            .with_api_key("key")
+            .with_base_url("http://localhost:8080/")
+            .build()
+            .unwrap();
+        assert_eq!(client.base_url(), "http://localhost:8080");
+
+        let err = Anthropic::builder()
+            .with_api_key("key")
+            .with_header("bad header", "value")
+            .build();
+        assert!(matches!(err, Err(Error::InvalidHeaderName(_))));
+    }
+
+    /// The body of a messages response, with 100 input and 10 output tokens.
+    pub(crate) fn message(content: Value, stop_reason: &str) -> Value {
+        json!({
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": DEFAULT_MODEL,
+            "content": content,
+            "stop_reason": stop_reason,
+            "stop_sequence": null,
+            "usage": {"input_tokens": 100, "output_tokens": 10}
+        })
+    }
+
+    /// A mock messages response.
+    pub(crate) fn message_response(content: Value, stop_reason: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(message(content, stop_reason))
+    }
+
+    /// A mock messages response with a single text block.
+    pub(crate) fn text_response(text: &str) -> ResponseTemplate {
+        message_response(json!([{"type": "text", "text": text}]), "end_turn")
+    }
+
+    /// The first event of a streamed message, with the given input tokens and 1 output token.
+    pub(crate) fn message_start(id: &str, input_tokens: u32) -> Value {
+        json!({"type": "message_start", "message": {
+            "id": id,
+            "type": "message",
+            "role": "assistant",
+            "model": DEFAULT_MODEL,
+            "content": [],
+            "stop_reason": null,
+            "stop_sequence": null,
+            "usage": {"input_tokens": input_tokens, "output_tokens": 1}
+        }})
+    }
+
+    /// A mock streaming response with these events.
+    pub(crate) fn sse(events: &[Value]) -> ResponseTemplate {
+        let body: String = events
+            .iter()
+            .map(|event| format!("data: {event}\n\n"))
+            .collect();
+        ResponseTemplate::new(200).set_body_raw(body, "text/event-stream")
+    }
+
+    #[tokio::test]
+    async fn test_builder_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(header("x-api-key", "key"))
+            .and(header("x-gateway", "team"))
+            .and(header("user-agent", "test-agent"))
+            .respond_with(text_response("Hello"))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = Anthropic::builder()
+            .with_api_key("key")
+            .with_base_url(server.uri())
+            .with_header("x-gateway", "team")
+            .with_user_agent("test-agent")
+            .with_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::text("Hi"));
+        // Clones share the same configuration and connection pool.
+        for client in [client.clone(), client] {
+            let response = client.messages(&request).await.unwrap();
+            assert_eq!(response.format_content(), "Hello");
+        }
+    }
+
+    pub(crate) fn test_client(server: &MockServer) -> Anthropic {
+        Anthropic::builder()
+            .with_api_key("key")
+            .with_base_url(server.uri())
+            .with_retry_policy(RetryPolicy::default().with_base_delay(Duration::ZERO))
+            .build()
+            .unwrap()
+    }
+
+    fn error_response(status: u16, error_type: &str) -> ResponseTemplate {
+        ResponseTemplate::new(status).set_body_json(json!({
+            "type": "error",
+            "error": {"type": error_type, "message": "try again"}
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(429, "rate_limit_error").insert_header("retry-after", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(529, "overloaded_error"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(text_response("Hello"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let response = test_client(&server)
+            .messages(&MessagesRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(response.format_content(), "Hello");
+
+        // Retries give up after max_retries, and client errors are never retried.
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(529, "overloaded_error"))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let err = test_client(&server)
+            .messages(&MessagesRequest::default())
+            .await;
+        assert!(matches!(err, Err(Error::ApiOverloaded(_))));
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(400, "invalid_request_error"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let err = test_client(&server)
+            .messages(&MessagesRequest::default())
+            .await;
+        assert!(matches!(err, Err(Error::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_retry_stream() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(error_response(529, "overloaded_error"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(sse(&[
+                message_start("msg_01", 1),
+                json!({"type": "content_block_start", "index": 0,
+                       "content_block": {"type": "text", "text": ""}}),
+                json!({"type": "content_block_delta", "index": 0,
+                       "delta": {"type": "text_delta", "text": "Hello"}}),
+                json!({"type": "content_block_stop", "index": 0}),
+                json!({"type": "message_stop"}),
+            ]))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let request = MessagesRequest::default().with_stream(true);
+        let mut stream = test_client(&server).messages_stream(&request).unwrap();
+        while let Some(event) = stream.next().await {
+            event.unwrap();
+        }
+        assert_eq!(stream.content_text(), "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_messages_with_continuation() {
+        let server = MockServer::start().await;
+        let response = |id: &str, content: Value, stop_reason: &str| {
+            let mut body = message(content, stop_reason);
+            body["id"] = id.into();
+            ResponseTemplate::new(200).set_body_json(body)
+        };
+        let searching = json!({"type": "server_tool_use", "id": "srvtoolu_01", "name": "web_search", "input": {"query": "rust"}});
+        Mock::given(method("POST"))
+            .respond_with(response("msg_01", json!([searching]), "pause_turn"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({"messages": [
+                {"role": "user", "content": [{"type": "text", "text": "Search for rust"}]},
+                {"role": "assistant", "content": [searching]}
+            ]})))
+            .respond_with(response(
+                "msg_02",
+                json!([{"type": "text", "text": "Found it"}]),
+                "end_turn",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::text("Search for rust"));
+        let response = test_client(&server)
+            .messages_with_continuation(&request, 3)
+            .await
+            .unwrap();
+        assert_eq!(response.id, "msg_02");
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(response.content.len(), 2);
+        assert_eq!(response.usage.output_tokens, Some(20));
+    }
+
+    #[tokio::test]
+    async fn test_count_tokens() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages/count_tokens"))
+            .and(body_json(json!({
+                "model": DEFAULT_MODEL,
+                "messages": [{"role": "user", "content": [{"type": "text", "text": "Hi"}]}],
+                "system": [{"type": "text", "text": "Be brief."}],
+                "thinking": {"type": "enabled", "budget_tokens": 1024}
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"input_tokens": 42})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut request = MessagesRequest::default()
+            .with_system(vec![Content::text("Be brief.")])
+            .with_thinking(1024)
+            .with_temperature(0.5)
+            .with_stream(true);
+        request.add_user(Content::text("Hi"));
+        let count = test_client(&server).count_tokens(&request).await.unwrap();
+        assert_eq!(count.input_tokens, 42);
+    }
+
+    #[test]
+    fn test_tool_choice() {
+        let request = MessagesRequest::default();
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("tool_choice").is_none());
+
+        let request = request.with_disable_parallel_tool_use(true);
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["tool_choice"],
+            json!({"type": "auto", "disable_parallel_tool_use": true})
+        );
+
+        let request = request.with_tool_choice(ToolChoice::tool("stockprice"));
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["tool_choice"],
+            json!({"type": "tool", "name": "stockprice"})
+        );
+
+        let choice: ToolChoice = serde_json::from_value(json!({"type": "any"})).unwrap();
+        assert_eq!(choice, ToolChoice::any());
+        let choice = choice.with_disable_parallel_tool_use(true);
+        assert_eq!(
+            serde_json::to_value(&choice).unwrap(),
+            json!({"type": "any", "disable_parallel_tool_use": true})
+        );
+    }
+
     #[test]
     fn test_cache_control_serialization() {
         let cache_control = CacheControl::Ephemeral;
@@ -1413,6 +3009,308 @@ mod tests {
         }
     }
 
+    fn stream_events(events: &[Value]) -> Result<StreamedResponse> {
+        let mut streamed = StreamedResponse {
+            response: MessagesResponse::default(),
+            event_source: None,
+            partial_json: HashMap::new(),
+            retry: None,
+        };
+        for event in events {
+            let event: StreamEvent = serde_json::from_value(event.clone()).unwrap();
+            streamed.merge_event(&event)?;
+        }
+        Ok(streamed)
+    }
+
+    #[test]
+    fn test_streamed_server_tool_use() {
+        let streamed = stream_events(&[
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": {
+                    "type": "server_tool_use",
+                    "id": "srvtoolu_01",
+                    "name": "web_search",
+                    "input": {}
+                }
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "input_json_delta", "partial_json": "{\"query\": \"rust\"}"}
+            }),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({
+                "type": "content_block_start",
+                "index": 1,
+                "content_block": {
+                    "type": "web_search_tool_result",
+                    "tool_use_id": "srvtoolu_01",
+                    "content": {
+                        "type": "web_search_tool_result_error",
+                        "error_code": "max_uses_exceeded"
+                    }
+                }
+            }),
+            json!({"type": "content_block_stop", "index": 1}),
+        ])
+        .unwrap();
+
+        let mut request = MessagesRequest::default();
+        request.merge_streamed_response(&streamed);
+        assert_eq!(
+            request.messages[0].format_content(),
+            "Assistant: [Server Tool(srvtoolu_01): web_search {\"query\":\"rust\"}]\n\
+             [Web Search Error(srvtoolu_01): max_uses_exceeded]"
+        );
+        assert_eq!(streamed.response.tool_uses().count(), 0);
+    }
+
+    #[test]
+    fn test_streamed_tool_use_input() {
+        let streamed = stream_events(&[
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": {"type": "text", "text": ""}
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "text_delta", "text": "Checking."}
+            }),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({
+                "type": "content_block_start",
+                "index": 1,
+                "content_block": {
+                    "type": "tool_use",
+                    "id": "toolu_01",
+                    "name": "stockprice",
+                    "input": {}
+                }
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 1,
+                "delta": {"type": "input_json_delta", "partial_json": ""}
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 1,
+                "delta": {"type": "input_json_delta", "partial_json": "{\"ticker\": "}
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 1,
+                "delta": {"type": "input_json_delta", "partial_json": "\"AAPL\"}"}
+            }),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({
+                "type": "content_block_start",
+                "index": 2,
+                "content_block": {
+                    "type": "tool_use",
+                    "id": "toolu_02",
+                    "name": "noargs",
+                    "input": {}
+                }
+            }),
+            json!({"type": "content_block_stop", "index": 2}),
+        ])
+        .unwrap();
+
+        assert_eq!(streamed.content_text(), "Checking.");
+        match &streamed.response.content[1] {
+            Content::ToolUse(tool_use) => {
+                assert_eq!(tool_use.id, "toolu_01");
+                assert_eq!(tool_use.input, json!({"ticker": "AAPL"}));
+            }
+            _ => panic!("Expected Content::ToolUse"),
+        }
+        match &streamed.response.content[2] {
+            Content::ToolUse(tool_use) => assert_eq!(tool_use.input, json!({})),
+            _ => panic!("Expected Content::ToolUse"),
+        }
+
+        let mut request = MessagesRequest::default();
+        request.merge_streamed_response(&streamed);
+        assert_eq!(request.messages[0].content.len(), 3);
+
+        // Malformed input is reported as an error rather than a panic.
+        let err = stream_events(&[
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": {
+                    "type": "tool_use",
+                    "id": "toolu_01",
+                    "name": "stockprice",
+                    "input": {}
+                }
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "input_json_delta", "partial_json": "{\"ticker\": "}
+            }),
+            json!({"type": "content_block_stop", "index": 0}),
+        ]);
+        assert!(matches!(
+            err,
+            Err(Error::ToolInputParseError { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_streamed_citations() {
+        let streamed = stream_events(&[
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": {"type": "text", "text": ""}
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {
+                    "type": "citations_delta",
+                    "citation": {
+                        "type": "page_location",
+                        "cited_text": "Revenue grew 12%.",
+                        "document_index": 0,
+                        "document_title": "Annual Report",
+                        "start_page_number": 3,
+                        "end_page_number": 4
+                    }
+                }
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "text_delta", "text": "Revenue grew."}
+            }),
+            json!({"type": "content_block_stop", "index": 0}),
+        ])
+        .unwrap();
+
+        let Content::Text(text) = &streamed.response.content[0] else {
+            panic!("Expected Content::Text");
+        };
+        assert_eq!(text.text, "Revenue grew.");
+        let citations = text.citations.as_ref().unwrap();
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].title(), Some("Annual Report"));
+        assert_eq!(citations[0].span(), Some(CitedSpan::Pages(3..4)));
+    }
+
+    #[test]
+    fn test_streamed_thinking_round_trip() {
+        let streamed = stream_events(&[
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": {"type": "thinking", "thinking": ""}
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "thinking_delta", "thinking": "Need the weather."}
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "signature_delta", "signature": "EqQBCgIYAhIM"}
+            }),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({
+                "type": "content_block_start",
+                "index": 1,
+                "content_block": {"type": "redacted_thinking", "data": "EmwKAhgBEgy3va3pzix"}
+            }),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({
+                "type": "content_block_start",
+                "index": 2,
+                "content_block": {
+                    "type": "tool_use",
+                    "id": "toolu_01",
+                    "name": "weather",
+                    "input": {}
+                }
+            }),
+            json!({"type": "content_block_stop", "index": 2}),
+        ])
+        .unwrap();
+
+        let mut request = MessagesRequest::default();
+        request.merge_streamed_response(&streamed);
+        assert_eq!(
+            serde_json::to_value(&request.messages[0].content).unwrap(),
+            json!([
+                {"type": "thinking", "thinking": "Need the weather.", "signature": "EqQBCgIYAhIM"},
+                {"type": "redacted_thinking", "data": "EmwKAhgBEgy3va3pzix"},
+                {"type": "tool_use", "id": "toolu_01", "name": "weather", "input": {}}
+            ])
+        );
+        assert_eq!(
+            request.messages[0].format_content(),
+            "Assistant: [Thinking: Need the weather.]\n[Redacted Thinking]\n[Tool(toolu_01): weather {}]"
+        );
+    }
+
+    #[test]
+    fn test_unknown_variants() {
+        let block = json!({"type": "hologram", "frames": [1, 2, 3]});
+        let response: MessagesResponse = serde_json::from_value(message(
+            json!([{"type": "text", "text": "Hi"}, block]),
+            "daydreaming",
+        ))
+        .unwrap();
+        assert_eq!(
+            response.stop_reason,
+            Some(StopReason::Unknown("daydreaming".into()))
+        );
+        assert_eq!(response.content[1].typ(), "hologram");
+
+        // Unknown blocks are sent back unchanged on the next turn.
+        let mut request = MessagesRequest::default();
+        request.merge_response(&response);
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["messages"][0]["content"][1], block);
+        assert_eq!(
+            serde_json::to_value(&response.stop_reason).unwrap(),
+            "daydreaming"
+        );
+
+        let streamed = stream_events(&[
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": {"type": "text", "text": ""}
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "sparkle_delta", "sparkle": true}
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "text_delta", "text": "Hi"}
+            }),
+            json!({"type": "telemetry", "progress": 0.5}),
+            json!({"type": "content_block_stop", "index": 0}),
+        ])
+        .unwrap();
+        assert_eq!(streamed.content_text(), "Hi");
+
+        let event: StreamEvent = serde_json::from_str(r#"{"type":"telemetry"}"#).unwrap();
+        assert!(matches!(event, StreamEvent::Unknown(_)));
+    }
+
     #[test]
     fn test_content_thinking() {
         // Test Content::Thinking serialization and deserialization
@@ -1450,6 +3348,79 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_tool_result_content() {
+        let tool_use = ToolUse::new("toolu_01".into(), "chart".into(), json!({}));
+        let text = Content::tool_result(&tool_use, "42");
+        let blocks = Content::tool_result_blocks(
+            &tool_use,
+            vec![
+                Content::text("Here is the chart"),
+                Content::image_url("https://example.com/chart.png"),
+            ],
+        );
+        let error = Content::tool_error(&tool_use, "chart service unavailable");
+
+        let json = serde_json::to_value([&text, &blocks, &error]).unwrap();
+        assert_eq!(
+            json,
+            json!([
+                {"type": "tool_result", "tool_use_id": "toolu_01", "content": "42"},
+                {"type": "tool_result", "tool_use_id": "toolu_01", "content": [
+                    {"type": "text", "text": "Here is the chart"},
+                    {"type": "image", "source": {"type": "url", "url": "https://example.com/chart.png"}}
+                ]},
+                {"type": "tool_result", "tool_use_id": "toolu_01", "content": "chart service unavailable", "is_error": true}
+            ])
+        );
+        let round_trip: Vec<Content> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&round_trip).unwrap(), json);
+
+        let mut message = Message::new(Role::User);
+        message.content = round_trip;
+        assert_eq!(
+            message.format_content(),
+            "User: [Tool Result(toolu_01): 42]\n\
+             [Tool Result(toolu_01): Here is the chart\n[Image: url https://example.com/chart.png]]\n\
+             [Tool Error(toolu_01): chart service unavailable]"
+        );
+    }
+
+    #[test]
+    fn test_content_document() {
+        let Content::Document(document) = Content::text_document("The grass is green.") else {
+            panic!("Expected Content::Document");
+        };
+        let document = document.with_title("Facts").with_citations(true);
+        assert_eq!(
+            serde_json::to_value(Content::Document(document.clone())).unwrap(),
+            json!({
+                "type": "document",
+                "source": {"type": "text", "media_type": "text/plain", "data": "The grass is green."},
+                "title": "Facts",
+                "citations": {"enabled": true}
+            })
+        );
+        assert_eq!(document.to_string(), "Facts (text)");
+
+        let custom = Document::new(DocumentSource::Content {
+            content: vec![Content::text("First chunk"), Content::text("Second chunk")],
+        });
+        let json = serde_json::to_value(Content::Document(custom)).unwrap();
+        assert_eq!(json["source"]["type"], "content");
+        assert_eq!(json["source"]["content"][1]["text"], "Second chunk");
+
+        let mut request = MessagesRequest::default();
+        request.add_user(Content::pdf_url("https://example.com/paper.pdf"));
+        assert!(request.betas().is_empty());
+        request.add_user(Content::document_file("file_01"));
+        assert_eq!(request.betas(), [FILES_API_BETA]);
+        assert_eq!(
+            request.messages[0].format_content(),
+            "User: [Document: url https://example.com/paper.pdf]\n[Document: file file_01]"
+        );
+    }
+
     #[test]
     fn test_messages_request_metadata_top_k_top_p() {
         // Test default values
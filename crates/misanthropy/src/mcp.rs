@@ -0,0 +1,219 @@
+//! Model Context Protocol support: the MCP connector, which lets the API call tools on remote
+//! MCP servers directly, and a client for local MCP servers.
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{CacheControl, ToolResultContent};
+
+mod client;
+
+pub use client::*;
+
+/// A remote MCP server whose tools the AI can use, added with `MessagesRequest::with_mcp_server`.
+///
+/// ```
+/// # use misanthropy::{McpServer, MessagesRequest};
+/// let request = MessagesRequest::default().with_mcp_server(
+///     McpServer::new("tickets", "https://mcp.example.com/sse")
+///         .with_authorization_token("secret")
+///         .with_allowed_tools(["search_tickets"]),
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename = "url")]
+pub struct McpServer {
+    /// The URL of the server, which must use HTTPS and Streamable HTTP or SSE.
+    pub url: String,
+    /// A unique name for the server, used in `McpToolUse::server_name`.
+    pub name: String,
+    /// An OAuth access token sent to the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization_token: Option<String>,
+    /// Which of the server's tools the AI may use. Defaults to all of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_configuration: Option<McpToolConfiguration>,
+}
+
+/// Which tools of an MCP server the AI may use.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct McpToolConfiguration {
+    /// Whether the server's tools are available. Defaults to true.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Only these tools are available. Defaults to all tools.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_tools: Option<Vec<String>>,
+}
+
+impl McpServer {
+    /// Creates a server with a unique name and its URL, allowing all of its tools.
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            name: name.into(),
+            authorization_token: None,
+            tool_configuration: None,
+        }
+    }
+
+    /// Sets the OAuth access token sent to the server.
+    pub fn with_authorization_token(mut self, token: impl Into<String>) -> Self {
+        self.authorization_token = Some(token.into());
+        self
+    }
+
+    /// Only allows the AI to use these tools from the server.
+    pub fn with_allowed_tools<T: Into<String>>(
+        mut self,
+        tools: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.tool_configuration
+            .get_or_insert_with(Default::default)
+            .allowed_tools = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Enables or disables all tools from the server.
+    pub fn with_tools_enabled(mut self, enabled: bool) -> Self {
+        self.tool_configuration
+            .get_or_insert_with(Default::default)
+            .enabled = Some(enabled);
+        self
+    }
+}
+
+/// A tool on an MCP server used by the AI. The API calls the tool and adds the result to the
+/// response as an `McpToolResult`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct McpToolUse {
+    /// A unique id, referred to by the `McpToolResult`.
+    pub id: String,
+    /// The name of the tool.
+    pub name: String,
+    /// The name of the `McpServer` with the tool.
+    pub server_name: String,
+    /// The input to the tool, matching its input schema.
+    pub input: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// The result of an MCP tool use.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct McpToolResult {
+    /// The id of the `McpToolUse` block.
+    pub tool_use_id: String,
+    #[serde(default)]
+    pub is_error: bool,
+    pub content: ToolResultContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+impl fmt::Display for McpToolUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[MCP Tool({}): {}/{} {}]",
+            self.id, self.server_name, self.name, self.input
+        )
+    }
+}
+
+impl fmt::Display for McpToolResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[MCP Tool {}({}): {}]",
+            if self.is_error { "Error" } else { "Result" },
+            self.tool_use_id,
+            self.content
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{tests::message, Content, MessagesRequest, MessagesResponse, Role};
+
+    #[test]
+    fn test_mcp_servers() {
+        let mut request = MessagesRequest::default()
+            .with_mcp_server(
+                McpServer::new("tickets", "https://mcp.example.com/sse")
+                    .with_authorization_token("secret")
+                    .with_allowed_tools(["search_tickets"]),
+            )
+            .with_mcp_server(
+                McpServer::new("wiki", "https://wiki.example.com/mcp").with_tools_enabled(false),
+            );
+        request.add_user(Content::text("Any open tickets about login?"));
+        assert_eq!(request.betas(), ["mcp-client-2025-04-04"]);
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["mcp_servers"],
+            json!([
+                {
+                    "type": "url",
+                    "url": "https://mcp.example.com/sse",
+                    "name": "tickets",
+                    "authorization_token": "secret",
+                    "tool_configuration": {"allowed_tools": ["search_tickets"]}
+                },
+                {
+                    "type": "url",
+                    "url": "https://wiki.example.com/mcp",
+                    "name": "wiki",
+                    "tool_configuration": {"enabled": false}
+                }
+            ])
+        );
+        let parsed: MessagesRequest = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.mcp_servers, request.mcp_servers);
+
+        let json = serde_json::to_value(MessagesRequest::default()).unwrap();
+        assert_eq!(json.get("mcp_servers"), None);
+    }
+
+    #[test]
+    fn test_mcp_tool_blocks() {
+        let fixture = json!([
+            {
+                "type": "mcp_tool_use",
+                "id": "mcptoolu_01",
+                "name": "search_tickets",
+                "server_name": "tickets",
+                "input": {"query": "login"}
+            },
+            {
+                "type": "mcp_tool_result",
+                "tool_use_id": "mcptoolu_01",
+                "is_error": false,
+                "content": [{"type": "text", "text": "TICKET-42: Login fails on Safari"}]
+            }
+        ]);
+        let response: MessagesResponse =
+            serde_json::from_value(message(fixture.clone(), "end_turn")).unwrap();
+        assert!(matches!(
+            &response.content[0],
+            Content::McpToolUse(tool_use) if tool_use.server_name == "tickets"
+        ));
+        assert_eq!(response.tool_uses().count(), 0);
+        assert_eq!(serde_json::to_value(&response.content).unwrap(), fixture);
+
+        let mut request = MessagesRequest::default();
+        request.merge_response(&response);
+        assert_eq!(request.messages[0].role, Role::Assistant);
+        assert_eq!(
+            request.messages[0].format_content(),
+            "Assistant: [MCP Tool(mcptoolu_01): tickets/search_tickets {\"query\":\"login\"}]\n\
+             [MCP Tool Result(mcptoolu_01): TICKET-42: Login fails on Safari]"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! A client for local MCP servers that communicate over stdio, which exposes their tools as
+//! misanthropy tools.
+use std::{collections::HashSet, ffi::OsStr, mem, process::Stdio, sync::Arc, time::Duration};
+
+use schemars::Schema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::Mutex,
+};
+
+use crate::{
+    Content, Error, Image, Result, Source, Tool, ToolError, ToolHandler, ToolResult,
+    ToolResultContent, ToolUse,
+};
+
+/// The MCP protocol version requested during the handshake.
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// How long to wait for a response to a request, unless set with `from_command_with_timeout`.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A connection to a local MCP server process. The server's tools can be added to a request
+/// with `tools`, and tool uses for them forwarded with `call`. Alternatively, `handlers` returns
+/// the tools as `ToolHandler`s, for use with `ToolRegistry` and `Agent`.
+///
+/// Requests to the server are sent one at a time, and fail if the server doesn't respond within
+/// a timeout of 60 seconds by default. The server process is killed when the last clone of the
+/// client is dropped.
+///
+/// ```no_run
+/// # use misanthropy::{McpClient, MessagesRequest, ToolRegistry};
+/// # async fn example() -> misanthropy::Result<()> {
+/// let client = McpClient::spawn("npx", ["-y", "@modelcontextprotocol/server-everything"]).await?;
+/// let mut tools = ToolRegistry::new();
+/// for handler in client.handlers() {
+///     tools.register(handler);
+/// }
+/// let request = MessagesRequest::default().with_tools(tools.tools()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct McpClient {
+    connection: Arc<Mutex<Connection>>,
+    server_name: String,
+    tools: Vec<Tool>,
+}
+
+#[derive(Debug)]
+struct Connection {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    /// The line being received, kept here so a timeout doesn't lose a partially read line.
+    line: Vec<u8>,
+    next_id: u64,
+    timeout: Duration,
+}
+
+/// A tool as described by `tools/list`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolInfo {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    input_schema: Schema,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolsPage {
+    tools: Vec<ToolInfo>,
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CallToolResult {
+    #[serde(default)]
+    content: Vec<McpContent>,
+    #[serde(default)]
+    structured_content: Option<Value>,
+    #[serde(default)]
+    is_error: bool,
+}
+
+/// A content block in an MCP tool result.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum McpContent {
+    Text {
+        text: String,
+    },
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Resource {
+        resource: EmbeddedResource,
+    },
+    ResourceLink {
+        uri: String,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EmbeddedResource {
+    uri: String,
+    #[serde(default)]
+    mime_type: Option<String>,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    blob: Option<String>,
+}
+
+impl McpClient {
+    /// Starts an MCP server process and connects to it.
+    pub async fn spawn(
+        program: impl AsRef<OsStr>,
+        args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+    ) -> Result<Self> {
+        let mut command = Command::new(program);
+        command.args(args);
+        Self::from_command(command).await
+    }
+
+    /// Starts an MCP server process from a command, e.g. one with environment variables or a
+    /// working directory set, and connects to it. The server's stderr is inherited.
+    pub async fn from_command(command: Command) -> Result<Self> {
+        Self::from_command_with_timeout(command, DEFAULT_TIMEOUT).await
+    }
+
+    /// Starts an MCP server process from a command like `from_command`, waiting up to `timeout`
+    /// for the response to each request, including the handshake.
+    pub async fn from_command_with_timeout(
+        mut command: Command,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut connection = Connection {
+            _child: child,
+            stdin,
+            stdout,
+            line: Vec::new(),
+            next_id: 0,
+            timeout,
+        };
+
+        let init = connection
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "misanthropy", "version": env!("CARGO_PKG_VERSION")}
+                }),
+            )
+            .await?;
+        let server_name = init["serverInfo"]["name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        connection
+            .notify("notifications/initialized", json!({}))
+            .await?;
+
+        let mut tools = Vec::new();
+        let mut cursor = None;
+        let mut cursors = HashSet::new();
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let page: ToolsPage =
+                serde_json::from_value(connection.request("tools/list", params).await?)?;
+            let Some(next_cursor) = page.next_cursor else {
+                tools.extend(page.tools.into_iter().map(custom_tool));
+                break;
+            };
+            // Guard against servers that would keep us paging forever.
+            if page.tools.is_empty() || !cursors.insert(next_cursor.clone()) {
+                return Err(Error::McpError(format!(
+                    "tools/list returned a repeated or empty page with cursor {next_cursor}"
+                )));
+            }
+            tools.extend(page.tools.into_iter().map(custom_tool));
+            cursor = Some(next_cursor);
+        }
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            server_name,
+            tools,
+        })
+    }
+
+    /// The name the server reported during the handshake.
+    pub fn server_name(&self) -> &str {
+        &self.server_name
+    }
+
+    /// The definitions of the server's tools, for `MessagesRequest::with_tools`.
+    pub fn tools(&self) -> Vec<Tool> {
+        self.tools.clone()
+    }
+
+    /// The server's tools as `ToolHandler`s, for `ToolRegistry::register`.
+    pub fn handlers(&self) -> impl Iterator<Item = McpTool> + '_ {
+        self.tools.iter().map(|tool| McpTool {
+            client: self.clone(),
+            tool: tool.clone(),
+        })
+    }
+
+    /// Forwards a tool use to the server with `tools/call`, returning its result. Errors from
+    /// the tool or the server are returned as error results, so the model can see them.
+    pub async fn call(&self, tool_use: &ToolUse) -> ToolResult {
+        let (content, is_error) = match self.call_tool(&tool_use.name, &tool_use.input).await {
+            Ok(result) => result,
+            Err(e) => (e.to_string().into(), true),
+        };
+        ToolResult {
+            is_error,
+            ..ToolResult::new(tool_use.id.clone(), content)
+        }
+    }
+
+    /// Calls a tool, returning its content and whether it reported an error.
+    async fn call_tool(&self, name: &str, arguments: &Value) -> Result<(ToolResultContent, bool)> {
+        let result = self
+            .connection
+            .lock()
+            .await
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        let result: CallToolResult = serde_json::from_value(result)?;
+
+        let mut blocks: Vec<Content> = result.content.into_iter().map(content_block).collect();
+        if blocks.is_empty() {
+            if let Some(structured) = result.structured_content {
+                blocks.push(Content::text(structured.to_string()));
+            }
+        }
+        let content = match &blocks[..] {
+            [Content::Text(text)] => text.text.clone().into(),
+            _ => blocks.into(),
+        };
+        Ok((content, result.is_error))
+    }
+}
+
+/// Converts a tool listed by the server to a tool definition.
+fn custom_tool(tool: ToolInfo) -> Tool {
+    Tool::Custom {
+        name: tool.name,
+        description: tool.description.unwrap_or_default(),
+        input_schema: tool.input_schema,
+        cache_control: None,
+    }
+}
+
+/// Converts an MCP content block to a tool result block.
+fn content_block(content: McpContent) -> Content {
+    match content {
+        McpContent::Text { text } => Content::text(text),
+        McpContent::Image { data, mime_type } => Content::Image(Image::new(Source::Base64 {
+            media_type: mime_type,
+            data,
+        })),
+        McpContent::Resource { resource } => match resource {
+            EmbeddedResource {
+                text: Some(text), ..
+            } => Content::text(text),
+            EmbeddedResource {
+                blob: Some(data),
+                mime_type: Some(mime_type),
+                ..
+            } if mime_type.starts_with("image/") => Content::Image(Image::new(Source::Base64 {
+                media_type: mime_type,
+                data,
+            })),
+            EmbeddedResource { uri, .. } => Content::text(format!("[Resource: {uri}]")),
+        },
+        McpContent::ResourceLink { uri } => Content::text(format!("[Resource: {uri}]")),
+        McpContent::Unsupported => Content::text("[Unsupported content]"),
+    }
+}
+
+impl Connection {
+    /// Sends a JSON-RPC request and waits for its response, up to the timeout.
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let timeout = self.timeout;
+        tokio::time::timeout(timeout, self.exchange(method, params))
+            .await
+            .unwrap_or_else(|_| {
+                Err(Error::McpError(format!(
+                    "{method} timed out after {} seconds",
+                    timeout.as_secs_f32()
+                )))
+            })
+    }
+
+    /// Sends a JSON-RPC request and waits for its response. A response that arrives after
+    /// the request timed out is skipped by the next request, since its id doesn't match.
+    async fn exchange(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(&json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+            .await?;
+
+        loop {
+            let message = self.receive().await?;
+            if let Some(request) = message.get("method").and_then(Value::as_str) {
+                // A request or notification from the server. Notifications are ignored, and
+                // requests other than ping are refused since we advertise no capabilities.
+                if let Some(request_id) = message.get("id") {
+                    let reply = if request == "ping" {
+                        json!({"jsonrpc": "2.0", "id": request_id, "result": {}})
+                    } else {
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": request_id,
+                            "error": {"code": -32601, "message": "Method not found"}
+                        })
+                    };
+                    self.send(&reply).await?;
+                }
+                continue;
+            }
+            if message.get("id").and_then(Value::as_u64) != Some(id) {
+                log::debug!("Ignoring MCP response to another request: {message}");
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                return Err(Error::McpError(format!(
+                    "{method} failed: {}",
+                    error["message"].as_str().unwrap_or("unknown error")
+                )));
+            }
+            return Ok(message.get("result").cloned().unwrap_or_default());
+        }
+    }
+
+    /// Sends a JSON-RPC notification.
+    async fn notify(&mut self, method: &str, params: Value) -> Result<()> {
+        self.send(&json!({"jsonrpc": "2.0", "method": method, "params": params}))
+            .await
+    }
+
+    async fn send(&mut self, message: &Value) -> Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        self.stdin.write_all(&line).await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Value> {
+        loop {
+            // Unlike `read_line`, `read_until` keeps partially read data if it is cancelled.
+            if self.stdout.read_until(b'\n', &mut self.line).await? == 0 {
+                return Err(Error::McpError("the server closed its connection".into()));
+            }
+            let line = mem::take(&mut self.line);
+            if !line.trim_ascii().is_empty() {
+                return Ok(serde_json::from_slice(&line)?);
+            }
+        }
+    }
+}
+
+/// A tool on a local MCP server, usable as a `ToolHandler`.
+#[derive(Debug, Clone)]
+pub struct McpTool {
+    client: McpClient,
+    tool: Tool,
+}
+
+impl ToolHandler for McpTool {
+    type Input = Value;
+
+    fn name(&self) -> &str {
+        self.tool.name()
+    }
+
+    fn definition(&self) -> Result<Tool> {
+        Ok(self.tool.clone())
+    }
+
+    async fn call(&self, input: Value) -> std::result::Result<ToolResultContent, ToolError> {
+        match self.client.call_tool(self.tool.name(), &input).await? {
+            (content, false) => Ok(content),
+            (content, true) => Err(content.to_string().into()),
+        }
+    }
+}
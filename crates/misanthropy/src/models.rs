@@ -0,0 +1,103 @@
+//! The Models API.
+use serde::{Deserialize, Serialize};
+
+use crate::{Anthropic, ListParams, Paginated, Result};
+
+/// Information about a model available through the API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// Unique model identifier, e.g. `claude-sonnet-4-20250514`.
+    pub id: String,
+    /// A human-readable name for the model.
+    pub display_name: String,
+    /// RFC 3339 timestamp of when the model was released.
+    pub created_at: String,
+    /// Always "model".
+    #[serde(rename = "type")]
+    pub model_type: String,
+}
+
+impl Anthropic {
+    /// Lists available models, most recently released first. Pages are fetched as the stream is
+    /// consumed.
+    pub fn list_models(&self, params: ListParams) -> Paginated<ModelInfo> {
+        self.paginate("/v1/models", params, &[])
+    }
+
+    /// Retrieves a model by id or alias, e.g. `claude-sonnet-4-0`.
+    pub async fn get_model(&self, model_id: &str) -> Result<ModelInfo> {
+        let response = self
+            .send(self.get(&format!("/v1/models/{model_id}"))?)
+            .await?;
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param, query_param_is_missing},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::tests::test_client;
+
+    fn model(id: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "display_name": id,
+            "created_at": "2025-05-14T00:00:00Z",
+            "type": "model"
+        })
+    }
+
+    #[tokio::test]
+    async fn test_list_models() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .and(query_param("limit", "2"))
+            .and(query_param_is_missing("after_id"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [model("a"), model("b")],
+                "has_more": true,
+                "first_id": "a",
+                "last_id": "b"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .and(query_param("after_id", "b"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [model("c")],
+                "has_more": false,
+                "first_id": "c",
+                "last_id": "c"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models/c"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(model("c")))
+            .mount(&server)
+            .await;
+
+        let client = test_client(&server);
+        let models: Vec<ModelInfo> = client
+            .list_models(ListParams::default().with_limit(2))
+            .try_collect()
+            .await
+            .unwrap();
+        let ids: Vec<&str> = models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+
+        let model = client.get_model("c").await.unwrap();
+        assert_eq!(model.display_name, "c");
+    }
+}
@@ -0,0 +1,117 @@
+//! Pagination for list endpoints.
+use std::{collections::VecDeque, pin::Pin};
+
+use futures_util::{stream, Stream};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Anthropic, Result};
+
+/// A page of results from a list endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    /// The items on this page.
+    pub data: Vec<T>,
+    /// Whether there are more results in the direction being paginated.
+    pub has_more: bool,
+    /// The id of the first item on the page, used as `before_id` to fetch the previous page.
+    pub first_id: Option<String>,
+    /// The id of the last item on the page, used as `after_id` to fetch the next page.
+    pub last_id: Option<String>,
+}
+
+/// Parameters for list endpoints.
+///
+/// By default, results are listed from the most recent. Setting `before_id` pages backwards
+/// from an item instead, and setting `after_id` pages forwards.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ListParams {
+    /// Number of items to fetch per page. The API default is 20, and the maximum is 1000.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// List items immediately after this id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_id: Option<String>,
+    /// List items immediately before this id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_id: Option<String>,
+}
+
+impl ListParams {
+    /// Sets the number of items to fetch per page.
+    pub fn with_limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Lists items immediately after this id.
+    pub fn with_after_id(mut self, after_id: impl Into<String>) -> Self {
+        self.after_id = Some(after_id.into());
+        self
+    }
+
+    /// Lists items immediately before this id.
+    pub fn with_before_id(mut self, before_id: impl Into<String>) -> Self {
+        self.before_id = Some(before_id.into());
+        self
+    }
+
+    /// The parameters for the page following `page`, or `None` if it was the last page.
+    fn next(&self, page: &Page<impl Sized>) -> Option<Self> {
+        if !page.has_more {
+            return None;
+        }
+        let mut next = self.clone();
+        if self.before_id.is_some() {
+            next.before_id = Some(page.first_id.clone()?);
+        } else {
+            next.after_id = Some(page.last_id.clone()?);
+        }
+        Some(next)
+    }
+}
+
+/// A stream of items from a list endpoint. Pages are fetched as the stream is consumed.
+pub type Paginated<T> = Pin<Box<dyn Stream<Item = Result<T>> + Send>>;
+
+impl Anthropic {
+    /// Fetches a single page from a list endpoint, with the given beta features enabled.
+    pub(crate) async fn list_page<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &ListParams,
+        betas: &[&str],
+    ) -> Result<Page<T>> {
+        let request = self.request(reqwest::Method::GET, path, betas)?;
+        let response = self.send(request.query(params)).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Streams every item from a list endpoint, following `has_more` across pages.
+    pub(crate) fn paginate<T>(
+        &self,
+        path: impl Into<String>,
+        params: ListParams,
+        betas: &'static [&'static str],
+    ) -> Paginated<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let state = (self.clone(), path.into(), Some(params), VecDeque::new());
+        Box::pin(stream::try_unfold(
+            state,
+            move |(client, path, mut params, mut items)| async move {
+                loop {
+                    if let Some(item) = items.pop_front() {
+                        return Ok(Some((item, (client, path, params, items))));
+                    }
+                    let Some(current) = params.take() else {
+                        return Ok(None);
+                    };
+                    let page: Page<T> = client.list_page(&path, &current, betas).await?;
+                    params = current.next(&page);
+                    items.extend(page.data);
+                }
+            },
+        ))
+    }
+}
@@ -0,0 +1,250 @@
+//! Typed tool handlers and a registry that dispatches tool use requests to them.
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+use futures_util::future::join_all;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{Content, MessagesResponse, Result, Tool, ToolResult, ToolResultContent, ToolUse};
+
+/// An error returned by a tool handler. It is reported to the AI as a tool result with
+/// `is_error` set, so the model can correct its input or try something else.
+pub type ToolError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A tool implemented by the application, with typed input.
+///
+/// ```no_run
+/// use misanthropy::{ToolError, ToolHandler, ToolResultContent};
+/// use schemars::JsonSchema;
+/// use serde::Deserialize;
+///
+/// /// Get the current stock price for a given ticker symbol.
+/// #[derive(JsonSchema, Deserialize)]
+/// struct GetStockPrice {
+///     /// The stock ticker symbol, e.g. AAPL for Apple Inc.
+///     ticker: String,
+/// }
+///
+/// struct StockPrice;
+///
+/// impl ToolHandler for StockPrice {
+///     type Input = GetStockPrice;
+///
+///     fn name(&self) -> &str {
+///         "stockprice"
+///     }
+///
+///     async fn call(&self, input: GetStockPrice) -> Result<ToolResultContent, ToolError> {
+///         Ok(format!("{} is trading at $150.00", input.ticker).into())
+///     }
+/// }
+/// ```
+pub trait ToolHandler: Send + Sync + 'static {
+    /// The input of the tool. Its JSON schema and doc comment describe the tool to the AI.
+    type Input: JsonSchema + DeserializeOwned + Send;
+
+    /// The name of the tool.
+    fn name(&self) -> &str;
+
+    /// The tool definition sent to the API. Defaults to a custom tool derived from `Input`.
+    fn definition(&self) -> Result<Tool> {
+        Tool::custom::<Self::Input>(self.name())
+    }
+
+    /// Runs the tool with input from the AI.
+    fn call(
+        &self,
+        input: Self::Input,
+    ) -> impl Future<Output = std::result::Result<ToolResultContent, ToolError>> + Send;
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A `ToolHandler` with its input type erased, so handlers can be stored together.
+trait DynToolHandler: Send + Sync {
+    fn name(&self) -> &str;
+    fn definition(&self) -> Result<Tool>;
+    fn call(
+        &self,
+        input: Value,
+    ) -> BoxFuture<'_, std::result::Result<ToolResultContent, ToolError>>;
+}
+
+impl<T: ToolHandler> DynToolHandler for T {
+    fn name(&self) -> &str {
+        ToolHandler::name(self)
+    }
+
+    fn definition(&self) -> Result<Tool> {
+        ToolHandler::definition(self)
+    }
+
+    fn call(
+        &self,
+        input: Value,
+    ) -> BoxFuture<'_, std::result::Result<ToolResultContent, ToolError>> {
+        Box::pin(async move {
+            let input = serde_json::from_value(input)
+                .map_err(|e| format!("Invalid input for tool {}: {e}", ToolHandler::name(self)))?;
+            ToolHandler::call(self, input).await
+        })
+    }
+}
+
+/// A set of tool handlers. Generates the tool definitions for a request, and runs the tools the
+/// AI asks for in a response.
+///
+/// ```no_run
+/// # use misanthropy::{Anthropic, Content, MessagesRequest, ToolRegistry};
+/// # async fn example(client: Anthropic, tools: ToolRegistry) -> misanthropy::Result<()> {
+/// let mut request = MessagesRequest::default().with_tools(tools.tools()?);
+/// request.add_user(Content::text("What is Apple's stock price today?"));
+/// let response = client.messages(&request).await?;
+/// request.merge_response(&response);
+/// for result in tools.run(&response).await {
+///     request.add_user(result);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    handlers: Vec<Arc<dyn DynToolHandler>>,
+}
+
+impl fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.handlers.iter().map(|h| h.name()))
+            .finish()
+    }
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tool handler, replacing any handler with the same name.
+    pub fn register(&mut self, handler: impl ToolHandler) {
+        self.handlers
+            .retain(|h| h.name() != ToolHandler::name(&handler));
+        self.handlers.push(Arc::new(handler));
+    }
+
+    /// Adds a tool handler, replacing any handler with the same name.
+    pub fn with_handler(mut self, handler: impl ToolHandler) -> Self {
+        self.register(handler);
+        self
+    }
+
+    /// The definitions of all registered tools, for `MessagesRequest::with_tools`.
+    pub fn tools(&self) -> Result<Vec<Tool>> {
+        self.handlers.iter().map(|h| h.definition()).collect()
+    }
+
+    /// Runs a single tool use, returning its tool result block.
+    pub async fn call(&self, tool_use: &ToolUse) -> Content {
+        Content::ToolResult(self.tool_result(tool_use).await)
+    }
+
+    /// Runs a single tool use, returning its result.
+    pub(crate) async fn tool_result(&self, tool_use: &ToolUse) -> ToolResult {
+        let Some(handler) = self.handlers.iter().find(|h| h.name() == tool_use.name) else {
+            return error_result(tool_use, format!("Unknown tool: {}", tool_use.name));
+        };
+        match handler.call(tool_use.input.clone()).await {
+            Ok(content) => ToolResult::new(tool_use.id.clone(), content),
+            Err(e) => error_result(tool_use, e.to_string()),
+        }
+    }
+
+    /// Runs every tool use in a response concurrently, returning the tool result blocks in the
+    /// same order. Add them to the next user message to continue the conversation.
+    pub async fn run(&self, response: &MessagesResponse) -> Vec<Content> {
+        join_all(response.tool_uses().map(|tool_use| self.call(tool_use))).await
+    }
+}
+
+fn error_result(tool_use: &ToolUse, message: String) -> ToolResult {
+    ToolResult {
+        is_error: true,
+        ..ToolResult::new(tool_use.id.clone(), message)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    /// Add two numbers.
+    #[derive(JsonSchema, Deserialize)]
+    pub(crate) struct AddInput {
+        a: i64,
+        b: i64,
+    }
+
+    pub(crate) struct Add;
+
+    impl ToolHandler for Add {
+        type Input = AddInput;
+
+        fn name(&self) -> &str {
+            "add"
+        }
+
+        async fn call(&self, input: AddInput) -> std::result::Result<ToolResultContent, ToolError> {
+            let sum = input.a.checked_add(input.b).ok_or("overflow")?;
+            Ok(sum.to_string().into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry() {
+        let registry = ToolRegistry::new().with_handler(Add);
+        let tools = registry.tools().unwrap();
+        assert!(
+            matches!(&tools[..], [Tool::Custom { name, description, .. }]
+            if name == "add" && description == "Add two numbers.")
+        );
+
+        let tool_use = |id: &str, name: &str, input| {
+            Content::ToolUse(ToolUse::new(id.into(), name.into(), input))
+        };
+        let response = MessagesResponse {
+            content: vec![
+                Content::text("Let me add those."),
+                tool_use("toolu_01", "add", json!({"a": 2, "b": 3})),
+                tool_use("toolu_02", "add", json!({"a": "two"})),
+                tool_use("toolu_03", "add", json!({"a": i64::MAX, "b": 1})),
+                tool_use("toolu_04", "subtract", json!({})),
+            ],
+            ..Default::default()
+        };
+        let results = registry.run(&response).await;
+        let results: Vec<_> = results
+            .iter()
+            .map(|result| match result {
+                Content::ToolResult(result) => (
+                    result.tool_use_id.as_str(),
+                    result.content.to_string(),
+                    result.is_error,
+                ),
+                _ => panic!("Expected Content::ToolResult"),
+            })
+            .collect();
+        assert_eq!(results[0], ("toolu_01", "5".into(), false));
+        assert_eq!(results[1].0, "toolu_02");
+        assert!(results[1].1.starts_with("Invalid input for tool add"));
+        assert!(results[1].2);
+        assert_eq!(results[2], ("toolu_03", "overflow".into(), true));
+        assert_eq!(
+            results[3],
+            ("toolu_04", "Unknown tool: subtract".into(), true)
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Automatic retries for transient API failures.
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+
+use crate::{error::is_connection_error, Error};
+
+/// The longest `retry-after` delay that is honored. Longer delays fall back to the backoff
+/// schedule rather than stalling the client.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Controls how the client retries requests that fail with transient errors.
+///
+/// Failed requests are retried with exponential backoff. If the API supplies a `retry-after`
+/// header of at most a minute, its value is used as the delay instead. Streaming requests are
+/// only retried if the failure happens before the first event is received.
+///
+/// The default policy retries up to two times on rate limits (HTTP 429), overloads (HTTP 529),
+/// other server errors (HTTP 5xx) and connection failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the initial attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry. Doubles with each subsequent retry.
+    pub base_delay: Duration,
+    /// Upper bound on the backoff delay. Does not limit delays of up to a minute requested
+    /// through `retry-after`.
+    pub max_delay: Duration,
+    /// Randomize backoff delays to avoid many clients retrying in lockstep.
+    pub jitter: bool,
+    /// Retry rate limit errors (HTTP 429).
+    pub retry_rate_limit: bool,
+    /// Retry overloaded errors (HTTP 529).
+    pub retry_overloaded: bool,
+    /// Retry other server errors (HTTP 500-599).
+    pub retry_server_errors: bool,
+    /// Retry connection failures, resets and timeouts.
+    pub retry_connection_errors: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+            jitter: true,
+            retry_rate_limit: true,
+            retry_overloaded: true,
+            retry_server_errors: true,
+            retry_connection_errors: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the maximum number of retries after the initial attempt.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the delay before the first retry.
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Sets the upper bound on the backoff delay.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Enables or disables randomized backoff delays.
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Enables or disables retrying rate limit errors (HTTP 429).
+    pub fn with_retry_rate_limit(mut self, retry: bool) -> Self {
+        self.retry_rate_limit = retry;
+        self
+    }
+
+    /// Enables or disables retrying overloaded errors (HTTP 529).
+    pub fn with_retry_overloaded(mut self, retry: bool) -> Self {
+        self.retry_overloaded = retry;
+        self
+    }
+
+    /// Enables or disables retrying other server errors (HTTP 500-599).
+    pub fn with_retry_server_errors(mut self, retry: bool) -> Self {
+        self.retry_server_errors = retry;
+        self
+    }
+
+    /// Enables or disables retrying connection failures, resets and timeouts.
+    pub fn with_retry_connection_errors(mut self, retry: bool) -> Self {
+        self.retry_connection_errors = retry;
+        self
+    }
+
+    /// Should a request that failed with this error be retried?
+    pub fn retries(&self, error: &Error) -> bool {
+        match error {
+            Error::RateLimitExceeded(_) => self.retry_rate_limit,
+            Error::ApiOverloaded(_) => self.retry_overloaded,
+            Error::HttpError(e) => self.retry_connection_errors && is_connection_error(e),
+            error => self.retry_server_errors && error.is_retryable(),
+        }
+    }
+
+    /// The delay before retry number `attempt`, counting from zero. A `retry_after` value
+    /// supplied by the API takes precedence over the backoff schedule, unless it is longer than
+    /// a minute.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after.filter(|d| *d <= MAX_RETRY_AFTER) {
+            return retry_after;
+        }
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        if self.jitter {
+            // Keep at least half the delay, and randomize the rest.
+            delay / 2 + delay.mul_f64(fastrand::f64() / 2.0)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Parses the delay requested by the API from the `retry-after-ms` or `retry-after` headers.
+/// Only the delay-seconds form of `retry-after` is supported.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    // Negative, non-finite and overflowing values are ignored.
+    let header = |name: &str, scale: f64| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .and_then(|v| Duration::try_from_secs_f64(v / scale).ok())
+    };
+    header("retry-after-ms", 1000.0).or_else(|| header("retry-after", 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::default()
+            .with_base_delay(Duration::from_secs(1))
+            .with_max_delay(Duration::from_secs(5))
+            .with_jitter(false);
+        assert_eq!(policy.delay(0, None), Duration::from_secs(1));
+        assert_eq!(policy.delay(2, None), Duration::from_secs(4));
+        assert_eq!(policy.delay(3, None), Duration::from_secs(5));
+        assert_eq!(policy.delay(30, None), Duration::from_secs(5));
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(30))),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(3600))),
+            Duration::from_secs(1)
+        );
+
+        let jittered = policy.with_jitter(true).delay(2, None);
+        assert!(jittered >= Duration::from_secs(2) && jittered <= Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_retries() {
+        let error = |status| Error::from_api_response(status, &HeaderMap::new(), String::new());
+        let policy = RetryPolicy::default();
+        assert!(policy.retries(&error(429)));
+        assert!(policy.retries(&error(529)));
+        assert!(policy.retries(&error(502)));
+        assert!(!policy.retries(&error(400)));
+        assert!(!policy.retries(&error(404)));
+
+        let policy = policy
+            .with_retry_rate_limit(false)
+            .with_retry_server_errors(false);
+        assert!(!policy.retries(&error(429)));
+        assert!(policy.retries(&error(529)));
+        assert!(!policy.retries(&error(500)));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert("retry-after", HeaderValue::from_static("3"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(1500)));
+        headers.insert("retry-after-ms", HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+        headers.insert("retry-after-ms", HeaderValue::from_static("-100"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+        headers.insert("retry-after-ms", HeaderValue::from_static("inf"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+
+        headers.remove("retry-after-ms");
+        headers.insert("retry-after", HeaderValue::from_static("1e20"));
+        assert_eq!(retry_after(&headers), None);
+        headers.insert("retry-after", HeaderValue::from_static("-5"));
+        assert_eq!(retry_after(&headers), None);
+        headers.insert("retry-after", HeaderValue::from_static("NaN"));
+        assert_eq!(retry_after(&headers), None);
+
+        // Huge but representable delays fall back to the backoff schedule.
+        headers.insert("retry-after", HeaderValue::from_static("1e9"));
+        let policy = RetryPolicy::default().with_jitter(false);
+        assert_eq!(
+            policy.delay(0, retry_after(&headers)),
+            Duration::from_millis(500)
+        );
+    }
+}
@@ -0,0 +1,786 @@
+//! Tools run by Anthropic on the API servers: web search, web fetch and code execution, and the
+//! content blocks with their results.
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    CacheControl, CitationsConfig, Content, Document, MessagesResponse, CODE_EXECUTION,
+    CODE_EXECUTION_NAME, WEB_FETCH, WEB_FETCH_NAME, WEB_SEARCH, WEB_SEARCH_NAME,
+};
+
+fn web_search_type<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    crate::tool_type(deserializer, "web_search_")
+}
+
+fn web_fetch_type<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    crate::tool_type(deserializer, "web_fetch_")
+}
+
+fn code_execution_type<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    crate::tool_type(deserializer, "code_execution_")
+}
+
+/// The web search server tool, which lets the AI search the web and cite the results.
+///
+/// ```
+/// # use misanthropy::{MessagesRequest, UserLocation, WebSearchTool};
+/// let request = MessagesRequest::default().with_web_search(
+///     WebSearchTool::new()
+///         .with_max_uses(3)
+///         .with_allowed_domains(["docs.rs", "crates.io"])
+///         .with_user_location(UserLocation::default().with_country("GB")),
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebSearchTool {
+    /// Must be equal to `WEB_SEARCH_NAME`.
+    pub name: String,
+    /// The type of the web search tool, e.g. `WEB_SEARCH`.
+    #[serde(rename = "type", deserialize_with = "web_search_type")]
+    pub typ: String,
+    /// The maximum number of searches per request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+    /// Only include results from these domains.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_domains: Option<Vec<String>>,
+    /// Never include results from these domains.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_domains: Option<Vec<String>>,
+    /// The user's location, used to localize results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_location: Option<UserLocation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+impl Default for WebSearchTool {
+    fn default() -> Self {
+        Self {
+            name: WEB_SEARCH_NAME.into(),
+            typ: WEB_SEARCH.into(),
+            max_uses: None,
+            allowed_domains: None,
+            blocked_domains: None,
+            user_location: None,
+            cache_control: None,
+        }
+    }
+}
+
+impl WebSearchTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of searches per request.
+    pub fn with_max_uses(mut self, max_uses: u32) -> Self {
+        self.max_uses = Some(max_uses);
+        self
+    }
+
+    /// Only include results from these domains. Can't be combined with blocked domains.
+    pub fn with_allowed_domains<T: Into<String>>(
+        mut self,
+        domains: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.allowed_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Never include results from these domains. Can't be combined with allowed domains.
+    pub fn with_blocked_domains<T: Into<String>>(
+        mut self,
+        domains: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.blocked_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the user's location, used to localize results.
+    pub fn with_user_location(mut self, user_location: UserLocation) -> Self {
+        self.user_location = Some(user_location);
+        self
+    }
+}
+
+/// The approximate location of the user, for localizing web search results.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(tag = "type", rename = "approximate")]
+pub struct UserLocation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Two letter ISO country code, e.g. `US`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// IANA time zone, e.g. `America/New_York`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+impl UserLocation {
+    pub fn with_city(mut self, city: impl Into<String>) -> Self {
+        self.city = Some(city.into());
+        self
+    }
+
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    pub fn with_country(mut self, country: impl Into<String>) -> Self {
+        self.country = Some(country.into());
+        self
+    }
+
+    pub fn with_timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = Some(timezone.into());
+        self
+    }
+}
+
+/// The web fetch server tool, which lets the AI read the full content of web pages and PDFs.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebFetchTool {
+    /// Must be equal to `WEB_FETCH_NAME`.
+    pub name: String,
+    /// The type of the web fetch tool, e.g. `WEB_FETCH`.
+    #[serde(rename = "type", deserialize_with = "web_fetch_type")]
+    pub typ: String,
+    /// The maximum number of fetches per request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+    /// Only fetch from these domains.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_domains: Option<Vec<String>>,
+    /// Never fetch from these domains.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_domains: Option<Vec<String>>,
+    /// Whether the AI may cite fetched documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citations: Option<CitationsConfig>,
+    /// Truncate fetched content to about this many tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_content_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+impl Default for WebFetchTool {
+    fn default() -> Self {
+        Self {
+            name: WEB_FETCH_NAME.into(),
+            typ: WEB_FETCH.into(),
+            max_uses: None,
+            allowed_domains: None,
+            blocked_domains: None,
+            citations: None,
+            max_content_tokens: None,
+            cache_control: None,
+        }
+    }
+}
+
+impl WebFetchTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of fetches per request.
+    pub fn with_max_uses(mut self, max_uses: u32) -> Self {
+        self.max_uses = Some(max_uses);
+        self
+    }
+
+    /// Only fetch from these domains. Can't be combined with blocked domains.
+    pub fn with_allowed_domains<T: Into<String>>(
+        mut self,
+        domains: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.allowed_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Never fetch from these domains. Can't be combined with allowed domains.
+    pub fn with_blocked_domains<T: Into<String>>(
+        mut self,
+        domains: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.blocked_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Enables or disables citations of fetched documents.
+    pub fn with_citations(mut self, enabled: bool) -> Self {
+        self.citations = Some(CitationsConfig { enabled });
+        self
+    }
+
+    /// Truncates fetched content to about this many tokens.
+    pub fn with_max_content_tokens(mut self, max_content_tokens: u32) -> Self {
+        self.max_content_tokens = Some(max_content_tokens);
+        self
+    }
+}
+
+/// The code execution server tool, which runs code in a sandbox on Anthropic's servers.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CodeExecutionTool {
+    /// Must be equal to `CODE_EXECUTION_NAME`.
+    pub name: String,
+    /// The type of the code execution tool, `CODE_EXECUTION` or `CODE_EXECUTION_PYTHON`.
+    #[serde(rename = "type", deserialize_with = "code_execution_type")]
+    pub typ: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+impl CodeExecutionTool {
+    pub fn new(typ: impl Into<String>) -> Self {
+        Self {
+            name: CODE_EXECUTION_NAME.into(),
+            typ: typ.into(),
+            cache_control: None,
+        }
+    }
+}
+
+impl Default for CodeExecutionTool {
+    fn default() -> Self {
+        Self::new(CODE_EXECUTION)
+    }
+}
+
+/// The result of a web search server tool use.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebSearchToolResult {
+    /// The id of the `Content::ServerToolUse` block with the search.
+    pub tool_use_id: String,
+    pub content: WebSearchToolResultContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// The pages found by a web search, or the reason it failed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum WebSearchToolResultContent {
+    Results(Vec<WebSearchResult>),
+    Error(WebSearchToolResultError),
+}
+
+/// A page found by a web search.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename = "web_search_result")]
+pub struct WebSearchResult {
+    pub url: String,
+    pub title: String,
+    /// The content of the page, which must be passed back unchanged in later turns.
+    pub encrypted_content: String,
+    /// When the page was last updated, e.g. `April 30, 2025`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_age: Option<String>,
+}
+
+/// The reason a web search failed, e.g. `max_uses_exceeded` or `too_many_requests`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename = "web_search_tool_result_error")]
+pub struct WebSearchToolResultError {
+    pub error_code: String,
+}
+
+impl fmt::Display for WebSearchToolResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.content {
+            WebSearchToolResultContent::Results(results) => {
+                let results: Vec<_> = results
+                    .iter()
+                    .map(|r| format!("{} ({})", r.title, r.url))
+                    .collect();
+                write!(
+                    f,
+                    "[Web Search Result({}): {}]",
+                    self.tool_use_id,
+                    results.join(", ")
+                )
+            }
+            WebSearchToolResultContent::Error(error) => write!(
+                f,
+                "[Web Search Error({}): {}]",
+                self.tool_use_id, error.error_code
+            ),
+        }
+    }
+}
+
+/// The result of a web fetch server tool use.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebFetchToolResult {
+    /// The id of the `Content::ServerToolUse` block with the fetch.
+    pub tool_use_id: String,
+    pub content: WebFetchToolResultContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// The fetched document, or the reason the fetch failed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum WebFetchToolResultContent {
+    #[serde(rename = "web_fetch_result")]
+    Result(WebFetchResult),
+    /// The fetch failed, e.g. with `url_not_accessible` or `unsupported_content_type`.
+    #[serde(rename = "web_fetch_tool_result_error")]
+    Error { error_code: String },
+}
+
+/// A fetched web page or PDF.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebFetchResult {
+    pub url: String,
+    #[serde(with = "document_block")]
+    pub content: Document,
+    /// When the content was fetched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retrieved_at: Option<String>,
+}
+
+impl fmt::Display for WebFetchToolResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.content {
+            WebFetchToolResultContent::Result(result) => write!(
+                f,
+                "[Web Fetch Result({}): {} ({})]",
+                self.tool_use_id, result.url, result.content
+            ),
+            WebFetchToolResultContent::Error { error_code } => {
+                write!(f, "[Web Fetch Error({}): {error_code}]", self.tool_use_id)
+            }
+        }
+    }
+}
+
+/// (De)serializes a `Document` with its `type` tag, as it appears outside `Content`.
+mod document_block {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::Document;
+
+    #[derive(Serialize)]
+    #[serde(tag = "type", rename = "document")]
+    struct Tagged<'a> {
+        #[serde(flatten)]
+        document: &'a Document,
+    }
+
+    pub fn serialize<S: Serializer>(document: &Document, serializer: S) -> Result<S::Ok, S::Error> {
+        Tagged { document }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Document, D::Error> {
+        Document::deserialize(deserializer)
+    }
+}
+
+/// The result of running code with `CODE_EXECUTION_PYTHON`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CodeExecutionToolResult {
+    /// The id of the `Content::ServerToolUse` block with the code.
+    pub tool_use_id: String,
+    pub content: CodeExecutionToolResultContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// The output of a code execution, or the reason it failed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum CodeExecutionToolResultContent {
+    #[serde(rename = "code_execution_result")]
+    Result(CodeExecutionResult),
+    /// The code couldn't be run, e.g. with `unavailable` or `execution_time_exceeded`.
+    #[serde(rename = "code_execution_tool_result_error")]
+    Error { error_code: String },
+}
+
+/// The result of running code or a bash command with the code execution tool.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CodeExecutionResult {
+    pub stdout: String,
+    pub stderr: String,
+    pub return_code: i32,
+    /// Files created by the code.
+    #[serde(default)]
+    pub content: Vec<CodeExecutionOutput>,
+}
+
+/// A file created by code execution, which can be downloaded with the Files API.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CodeExecutionOutput {
+    CodeExecutionOutput { file_id: String },
+    BashCodeExecutionOutput { file_id: String },
+}
+
+impl CodeExecutionOutput {
+    pub fn file_id(&self) -> &str {
+        match self {
+            Self::CodeExecutionOutput { file_id } | Self::BashCodeExecutionOutput { file_id } => {
+                file_id
+            }
+        }
+    }
+}
+
+impl fmt::Display for CodeExecutionResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "return code {}", self.return_code)?;
+        if !self.stdout.is_empty() {
+            write!(f, "\n{}", self.stdout.trim_end())?;
+        }
+        if !self.stderr.is_empty() {
+            write!(f, "\n{}", self.stderr.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CodeExecutionToolResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.content {
+            CodeExecutionToolResultContent::Result(result) => {
+                write!(f, "[Code Execution Result({}): {result}]", self.tool_use_id)
+            }
+            CodeExecutionToolResultContent::Error { error_code } => write!(
+                f,
+                "[Code Execution Error({}): {error_code}]",
+                self.tool_use_id
+            ),
+        }
+    }
+}
+
+/// The result of a bash command run with `CODE_EXECUTION`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BashCodeExecutionToolResult {
+    /// The id of the `Content::ServerToolUse` block with the command.
+    pub tool_use_id: String,
+    pub content: BashCodeExecutionToolResultContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// The output of a bash command, or the reason it failed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum BashCodeExecutionToolResultContent {
+    #[serde(rename = "bash_code_execution_result")]
+    Result(CodeExecutionResult),
+    /// The command couldn't be run, e.g. with `unavailable` or `output_file_too_large`.
+    #[serde(rename = "bash_code_execution_tool_result_error")]
+    Error { error_code: String },
+}
+
+impl fmt::Display for BashCodeExecutionToolResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.content {
+            BashCodeExecutionToolResultContent::Result(result) => {
+                write!(f, "[Bash Result({}): {result}]", self.tool_use_id)
+            }
+            BashCodeExecutionToolResultContent::Error { error_code } => {
+                write!(f, "[Bash Error({}): {error_code}]", self.tool_use_id)
+            }
+        }
+    }
+}
+
+/// The result of a file operation run with `CODE_EXECUTION`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TextEditorCodeExecutionToolResult {
+    /// The id of the `Content::ServerToolUse` block with the operation.
+    pub tool_use_id: String,
+    pub content: TextEditorCodeExecutionToolResultContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// The result of viewing, creating or editing a file in the code execution sandbox.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum TextEditorCodeExecutionToolResultContent {
+    #[serde(rename = "text_editor_code_execution_view_result")]
+    View {
+        file_type: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        num_lines: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start_line: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        total_lines: Option<u32>,
+    },
+    #[serde(rename = "text_editor_code_execution_create_result")]
+    Create { is_file_update: bool },
+    #[serde(rename = "text_editor_code_execution_str_replace_result")]
+    StrReplace {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        old_start: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        old_lines: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        new_start: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        new_lines: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lines: Option<Vec<String>>,
+    },
+    /// The operation failed, e.g. with `file_not_found`.
+    #[serde(rename = "text_editor_code_execution_tool_result_error")]
+    Error {
+        error_code: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error_message: Option<String>,
+    },
+}
+
+impl fmt::Display for TextEditorCodeExecutionToolResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id = &self.tool_use_id;
+        match &self.content {
+            TextEditorCodeExecutionToolResultContent::View { file_type, .. } => {
+                write!(f, "[Text Editor Result({id}): viewed {file_type}]")
+            }
+            TextEditorCodeExecutionToolResultContent::Create { is_file_update } => write!(
+                f,
+                "[Text Editor Result({id}): {}]",
+                if *is_file_update {
+                    "updated file"
+                } else {
+                    "created file"
+                }
+            ),
+            TextEditorCodeExecutionToolResultContent::StrReplace { .. } => {
+                write!(f, "[Text Editor Result({id}): replaced text]")
+            }
+            TextEditorCodeExecutionToolResultContent::Error {
+                error_code,
+                error_message,
+            } => match error_message {
+                Some(message) => write!(f, "[Text Editor Error({id}): {error_code}: {message}]"),
+                None => write!(f, "[Text Editor Error({id}): {error_code}]"),
+            },
+        }
+    }
+}
+
+impl MessagesResponse {
+    /// The pages found by web searches in this response, without duplicates, in the order they
+    /// were found.
+    pub fn sources(&self) -> Vec<&WebSearchResult> {
+        let mut sources: Vec<&WebSearchResult> = Vec::new();
+        for content in &self.content {
+            if let Content::WebSearchToolResult(WebSearchToolResult {
+                content: WebSearchToolResultContent::Results(results),
+                ..
+            }) = content
+            {
+                for result in results {
+                    if !sources.iter().any(|source| source.url == result.url) {
+                        sources.push(result);
+                    }
+                }
+            }
+        }
+        sources
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{tests::message, DocumentSource, Message, MessagesRequest, Role, Tool, WEB_SEARCH};
+
+    #[test]
+    fn test_server_tool_definitions() {
+        let request = MessagesRequest::default()
+            .with_web_search(
+                WebSearchTool::new()
+                    .with_max_uses(3)
+                    .with_blocked_domains(["example.com"])
+                    .with_user_location(UserLocation::default().with_city("Leeds")),
+            )
+            .with_web_fetch(WebFetchTool::new().with_citations(true))
+            .with_code_execution(CODE_EXECUTION);
+        assert_eq!(
+            request.betas(),
+            ["web-fetch-2025-09-10", "code-execution-2025-08-25"]
+        );
+
+        let json = serde_json::to_value(&request.tools).unwrap();
+        assert_eq!(
+            json,
+            json!([
+                {
+                    "name": "web_search",
+                    "type": WEB_SEARCH,
+                    "max_uses": 3,
+                    "blocked_domains": ["example.com"],
+                    "user_location": {"type": "approximate", "city": "Leeds"}
+                },
+                {
+                    "name": "web_fetch",
+                    "type": WEB_FETCH,
+                    "citations": {"enabled": true}
+                },
+                {"name": "code_execution", "type": CODE_EXECUTION}
+            ])
+        );
+        let tools: Vec<Tool> = serde_json::from_value(json).unwrap();
+        assert!(matches!(&tools[0], Tool::WebSearch(tool) if tool.max_uses == Some(3)));
+        assert!(matches!(tools[1], Tool::WebFetch(_)));
+        assert!(matches!(tools[2], Tool::CodeExecution(_)));
+    }
+
+    #[test]
+    fn test_server_tool_results() {
+        let response: MessagesResponse = serde_json::from_value(message(
+            json!([
+                {"type": "server_tool_use", "id": "srvtoolu_01", "name": "web_search",
+                 "input": {"query": "rust release"}},
+                {"type": "web_search_tool_result", "tool_use_id": "srvtoolu_01", "content": [
+                    {"type": "web_search_result", "url": "https://blog.rust-lang.org",
+                     "title": "Rust Blog", "encrypted_content": "abc", "page_age": "1 day ago"},
+                    {"type": "web_search_result", "url": "https://www.rust-lang.org",
+                     "title": "Rust", "encrypted_content": "def"}
+                ]},
+                {"type": "web_fetch_tool_result", "tool_use_id": "srvtoolu_02", "content": {
+                    "type": "web_fetch_result",
+                    "url": "https://blog.rust-lang.org",
+                    "content": {"type": "document", "title": "Rust Blog",
+                                "source": {"type": "text", "media_type": "text/plain",
+                                           "data": "Rust 2.0 is out"}},
+                    "retrieved_at": "2025-09-10T12:00:00Z"
+                }},
+                {"type": "web_fetch_tool_result", "tool_use_id": "srvtoolu_03",
+                 "content": {"type": "web_fetch_tool_result_error",
+                             "error_code": "url_not_accessible"}},
+                {"type": "code_execution_tool_result", "tool_use_id": "srvtoolu_04",
+                 "content": {"type": "code_execution_result", "stdout": "4\n", "stderr": "",
+                             "return_code": 0, "content": []}},
+                {"type": "bash_code_execution_tool_result", "tool_use_id": "srvtoolu_05",
+                 "content": {"type": "bash_code_execution_result", "stdout": "", "stderr": "",
+                             "return_code": 0,
+                             "content": [{"type": "bash_code_execution_output",
+                                          "file_id": "file_01"}]}},
+                {"type": "text_editor_code_execution_tool_result", "tool_use_id": "srvtoolu_06",
+                 "content": {"type": "text_editor_code_execution_tool_result_error",
+                             "error_code": "file_not_found",
+                             "error_message": "No such file: a.py"}},
+                {"type": "web_search_tool_result", "tool_use_id": "srvtoolu_07", "content": [
+                    {"type": "web_search_result", "url": "https://www.rust-lang.org",
+                     "title": "Rust", "encrypted_content": "def"}
+                ]},
+                {"type": "text_editor_code_execution_tool_result", "tool_use_id": "srvtoolu_08",
+                 "content": {"type": "text_editor_code_execution_view_result",
+                             "file_type": "text", "content": "print(2 + 2)\n",
+                             "num_lines": 1, "start_line": 1, "total_lines": 1}},
+                {"type": "text_editor_code_execution_tool_result", "tool_use_id": "srvtoolu_09",
+                 "content": {"type": "text_editor_code_execution_create_result",
+                             "is_file_update": false}},
+                {"type": "text_editor_code_execution_tool_result", "tool_use_id": "srvtoolu_10",
+                 "content": {"type": "text_editor_code_execution_str_replace_result",
+                             "old_start": 1, "old_lines": 1, "new_start": 1, "new_lines": 1,
+                             "lines": ["-print(2 + 2)", "+print(3 + 3)"]}}
+            ]),
+            "end_turn",
+        ))
+        .unwrap();
+
+        let sources: Vec<_> = response.sources().iter().map(|s| s.url.as_str()).collect();
+        assert_eq!(
+            sources,
+            ["https://blog.rust-lang.org", "https://www.rust-lang.org"]
+        );
+
+        match &response.content[2] {
+            Content::WebFetchToolResult(WebFetchToolResult {
+                content: WebFetchToolResultContent::Result(result),
+                ..
+            }) => assert!(matches!(
+                &result.content.source,
+                DocumentSource::Text { data, .. } if data == "Rust 2.0 is out"
+            )),
+            _ => panic!("Expected a web fetch result"),
+        }
+        match &response.content[5] {
+            Content::BashCodeExecutionToolResult(BashCodeExecutionToolResult {
+                content: BashCodeExecutionToolResultContent::Result(result),
+                ..
+            }) => assert_eq!(result.content[0].file_id(), "file_01"),
+            _ => panic!("Expected a bash code execution result"),
+        }
+
+        let message = Message {
+            role: Role::Assistant,
+            content: response.content.clone(),
+        };
+        assert_eq!(
+            message.format_content(),
+            "Assistant: [Server Tool(srvtoolu_01): web_search {\"query\":\"rust release\"}]\n\
+             [Web Search Result(srvtoolu_01): Rust Blog (https://blog.rust-lang.org), \
+             Rust (https://www.rust-lang.org)]\n\
+             [Web Fetch Result(srvtoolu_02): https://blog.rust-lang.org (Rust Blog (text))]\n\
+             [Web Fetch Error(srvtoolu_03): url_not_accessible]\n\
+             [Code Execution Result(srvtoolu_04): return code 0\n4]\n\
+             [Bash Result(srvtoolu_05): return code 0]\n\
+             [Text Editor Error(srvtoolu_06): file_not_found: No such file: a.py]\n\
+             [Web Search Result(srvtoolu_07): Rust (https://www.rust-lang.org)]\n\
+             [Text Editor Result(srvtoolu_08): viewed text]\n\
+             [Text Editor Result(srvtoolu_09): created file]\n\
+             [Text Editor Result(srvtoolu_10): replaced text]"
+        );
+        match &response.content[8] {
+            Content::TextEditorCodeExecutionToolResult(TextEditorCodeExecutionToolResult {
+                content: TextEditorCodeExecutionToolResultContent::View { num_lines, .. },
+                ..
+            }) => assert_eq!(*num_lines, Some(1)),
+            _ => panic!("Expected a text editor view result"),
+        }
+
+        // Blocks are sent back unchanged in the next request.
+        let json = serde_json::to_value(&response.content).unwrap();
+        assert_eq!(json[1]["content"][0]["type"], "web_search_result");
+        assert_eq!(json[1]["content"][1].get("page_age"), None);
+        assert_eq!(json[2]["content"]["content"]["type"], "document");
+        assert_eq!(json[2]["content"]["content"]["title"], "Rust Blog");
+        assert_eq!(
+            json[5]["content"]["content"][0]["type"],
+            "bash_code_execution_output"
+        );
+        assert_eq!(
+            json[8]["content"],
+            json!({"type": "text_editor_code_execution_view_result", "file_type": "text",
+                   "content": "print(2 + 2)\n", "num_lines": 1, "start_line": 1,
+                   "total_lines": 1})
+        );
+        assert_eq!(
+            json[10]["content"],
+            json!({"type": "text_editor_code_execution_str_replace_result", "old_start": 1,
+                   "old_lines": 1, "new_start": 1, "new_lines": 1,
+                   "lines": ["-print(2 + 2)", "+print(3 + 3)"]})
+        );
+    }
+}
@@ -1,8 +1,19 @@
-//! Type definitions for built-in tool use
+//! Type definitions and executors for built-in tool use
+use std::borrow::Cow;
+
+use schemars::{JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
 
+mod bash;
+mod computer;
+mod text_editor;
+
+pub use bash::BashExecutor;
+pub use computer::{ComputerBackend, ComputerExecutor};
+pub use text_editor::TextEditorExecutor;
+
 /// Commands for the built-in text editor tool.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "command", rename_all = "snake_case")]
 pub enum TextEditor {
     /// View the contents of a file at the specified path.
@@ -44,3 +55,202 @@ pub enum TextEditor {
         path: String,
     },
 }
+
+/// Commands for the built-in bash tool.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Bash {
+    /// Run a command in the persistent shell.
+    Command {
+        /// The command to run.
+        command: String,
+    },
+    /// Restart the shell, discarding its state.
+    Restart {
+        /// Must be true.
+        restart: bool,
+    },
+}
+
+/// Actions for the built-in computer use tool.
+///
+/// Actions that take an optional `text` hold those modifier keys, e.g. `shift` or `ctrl+alt`,
+/// while clicking or scrolling.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ComputerAction {
+    /// Capture the current display.
+    Screenshot,
+    /// Get the current position of the mouse cursor.
+    CursorPosition,
+    /// Move the mouse cursor.
+    MouseMove { coordinate: Coordinate },
+    /// Click the left mouse button, at the current position if no coordinate is given.
+    LeftClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<Coordinate>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    /// Click the right mouse button.
+    RightClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<Coordinate>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    /// Click the middle mouse button.
+    MiddleClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<Coordinate>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    /// Double click the left mouse button.
+    DoubleClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<Coordinate>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    /// Triple click the left mouse button.
+    TripleClick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<Coordinate>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    /// Press the left mouse button at `start_coordinate` and release it at `coordinate`.
+    LeftClickDrag {
+        start_coordinate: Coordinate,
+        coordinate: Coordinate,
+    },
+    /// Press the left mouse button without releasing it.
+    LeftMouseDown,
+    /// Release the left mouse button.
+    LeftMouseUp,
+    /// Press a key or key combination, e.g. `Return` or `ctrl+s`, in xdotool syntax.
+    Key { text: String },
+    /// Hold a key or key combination down for `duration` seconds.
+    HoldKey { text: String, duration: f64 },
+    /// Type a string of text.
+    Type { text: String },
+    /// Scroll by `scroll_amount` wheel clicks, at the current position if no coordinate is given.
+    Scroll {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        coordinate: Option<Coordinate>,
+        scroll_direction: ScrollDirection,
+        scroll_amount: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    /// Wait for `duration` seconds.
+    Wait { duration: f64 },
+    /// View a region of the display at full resolution.
+    Zoom { region: Region },
+}
+
+impl ComputerAction {
+    /// The coordinates this action refers to.
+    pub fn coordinates(&self) -> Vec<Coordinate> {
+        match self {
+            Self::MouseMove { coordinate } => vec![*coordinate],
+            Self::LeftClick { coordinate, .. }
+            | Self::RightClick { coordinate, .. }
+            | Self::MiddleClick { coordinate, .. }
+            | Self::DoubleClick { coordinate, .. }
+            | Self::TripleClick { coordinate, .. }
+            | Self::Scroll { coordinate, .. } => coordinate.iter().copied().collect(),
+            Self::LeftClickDrag {
+                start_coordinate,
+                coordinate,
+            } => vec![*start_coordinate, *coordinate],
+            Self::Zoom { region } => vec![region.top_left, region.bottom_right],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A position on the display in pixels, from the top left corner. Serialized as `[x, y]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "[u32; 2]", into = "[u32; 2]")]
+pub struct Coordinate {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Coordinate {
+    pub fn new(x: u32, y: u32) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<[u32; 2]> for Coordinate {
+    fn from([x, y]: [u32; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Coordinate> for [u32; 2] {
+    fn from(coordinate: Coordinate) -> Self {
+        [coordinate.x, coordinate.y]
+    }
+}
+
+impl JsonSchema for Coordinate {
+    fn schema_name() -> Cow<'static, str> {
+        "Coordinate".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        <[u32; 2]>::json_schema(generator)
+    }
+}
+
+/// A rectangle on the display. Serialized as `[x0, y0, x1, y1]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "[u32; 4]", into = "[u32; 4]")]
+pub struct Region {
+    pub top_left: Coordinate,
+    pub bottom_right: Coordinate,
+}
+
+impl From<[u32; 4]> for Region {
+    fn from([x0, y0, x1, y1]: [u32; 4]) -> Self {
+        Self {
+            top_left: Coordinate::new(x0, y0),
+            bottom_right: Coordinate::new(x1, y1),
+        }
+    }
+}
+
+impl From<Region> for [u32; 4] {
+    fn from(region: Region) -> Self {
+        [
+            region.top_left.x,
+            region.top_left.y,
+            region.bottom_right.x,
+            region.bottom_right.y,
+        ]
+    }
+}
+
+impl JsonSchema for Region {
+    fn schema_name() -> Cow<'static, str> {
+        "Region".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        <[u32; 4]>::json_schema(generator)
+    }
+}
+
+/// The direction of a scroll action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
@@ -0,0 +1,305 @@
+//! An executor for the built-in bash tool, backed by a persistent local shell.
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::Mutex,
+};
+
+use super::Bash;
+use crate::{Tool, ToolError, ToolHandler, ToolResultContent, BASH_37, BASH_NAME};
+
+/// Environment variables passed through to the shell. Everything else is removed, so secrets
+/// such as `ANTHROPIC_API_KEY` aren't visible to commands.
+const DEFAULT_ENV: &[&str] = &["PATH", "LANG", "LC_ALL", "TERM", "TZ"];
+
+/// Runs bash tool commands in one persistent shell, so the working directory, variables and
+/// other state carry over between commands, as the model expects.
+///
+/// The shell starts in the working directory with a scrubbed environment, and `HOME` set to the
+/// working directory. If a command leaves the working directory, the shell is moved back. This
+/// keeps the model on track but is not a security boundary: commands can still read and write
+/// any path the process can. Run the executor in a container or VM for isolation.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use misanthropy::{tools::BashExecutor, ToolRegistry};
+/// let bash = BashExecutor::new("/tmp/workspace")?.with_timeout(Duration::from_secs(60));
+/// let tools = ToolRegistry::new().with_handler(bash);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct BashExecutor {
+    root: PathBuf,
+    typ: String,
+    timeout: Duration,
+    max_output: usize,
+    env: Vec<(String, String)>,
+    shell: Mutex<Option<Shell>>,
+}
+
+/// A running shell process.
+#[derive(Debug)]
+struct Shell {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    /// Marks the end of a command's output, so it can be told apart from the output itself.
+    sentinel: String,
+}
+
+impl BashExecutor {
+    /// Creates an executor whose shell runs in `root`, using the `BASH_37` tool type. Commands
+    /// time out after two minutes, and output is truncated to 30,000 bytes.
+    pub fn new(root: impl AsRef<Path>) -> std::io::Result<Self> {
+        let env = DEFAULT_ENV
+            .iter()
+            .filter_map(|key| Some((key.to_string(), std::env::var(key).ok()?)))
+            .collect();
+        Ok(Self {
+            root: root.as_ref().canonicalize()?,
+            typ: BASH_37.into(),
+            timeout: Duration::from_secs(120),
+            max_output: 30_000,
+            env,
+            shell: Mutex::new(None),
+        })
+    }
+
+    /// Sets the bash tool type. This must match the model, see `BASH_37` and `BASH_35`.
+    pub fn with_type(mut self, typ: impl Into<String>) -> Self {
+        self.typ = typ.into();
+        self
+    }
+
+    /// Sets how long a command may run. The shell is restarted if a command times out.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of bytes of output returned to the model.
+    pub fn with_max_output(mut self, max_output: usize) -> Self {
+        self.max_output = max_output;
+        self
+    }
+
+    /// Sets an environment variable for the shell. Takes effect when the shell next starts.
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.env.retain(|(k, _)| *k != key);
+        self.env.push((key, value.into()));
+        self
+    }
+
+    /// Runs a command, returning the output for the model or an error message.
+    pub async fn execute(&self, command: Bash) -> Result<String, String> {
+        let mut shell = self.shell.lock().await;
+        let command = match command {
+            Bash::Restart { restart: false } => {
+                return Err("Set restart to true to restart the shell.".into());
+            }
+            Bash::Restart { restart: true } => {
+                if let Some(mut old) = shell.take() {
+                    let _ = old.child.kill().await;
+                }
+                *shell = Some(self.start().await?);
+                return Ok("Shell restarted.".into());
+            }
+            Bash::Command { command } => command,
+        };
+        if shell.is_none() {
+            *shell = Some(self.start().await?);
+        }
+        let running = shell.as_mut().expect("shell was just started");
+
+        match tokio::time::timeout(self.timeout, self.run(running, &command)).await {
+            Ok(Ok(output)) => Ok(output),
+            Ok(Err(e)) => {
+                *shell = None;
+                Err(format!(
+                    "The shell exited unexpectedly ({e}). It will be restarted."
+                ))
+            }
+            Err(_) => {
+                if let Some(mut old) = shell.take() {
+                    let _ = old.child.kill().await;
+                }
+                Err(format!(
+                    "Command timed out after {} seconds. The shell was restarted.",
+                    self.timeout.as_secs_f32()
+                ))
+            }
+        }
+    }
+
+    async fn start(&self) -> Result<Shell, String> {
+        let mut child = Command::new("bash")
+            .args(["--noprofile", "--norc"])
+            .current_dir(&self.root)
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .env("HOME", &self.root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start bash: {e}"))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        // Send errors to the same pipe as output, so the model sees them in order.
+        stdin
+            .write_all(b"exec 2>&1\n")
+            .await
+            .map_err(|e| format!("Failed to start bash: {e}"))?;
+        Ok(Shell {
+            child,
+            stdin,
+            stdout,
+            sentinel: format!("__misanthropy_done_{:016x}__", fastrand::u64(..)),
+        })
+    }
+
+    /// Runs a command in the shell and reads its output up to the sentinel line.
+    async fn run(&self, shell: &mut Shell, command: &str) -> std::io::Result<String> {
+        let root = shell_quote(&self.root.to_string_lossy());
+        let script = format!(
+            "{command}\n__status=$?; case \"$PWD/\" in {root}/*) ;; *) cd {root}; \
+             echo \"[moved back to $PWD]\";; esac; printf '\\n{} %s\\n' \"$__status\"\n",
+            shell.sentinel
+        );
+        shell.stdin.write_all(script.as_bytes()).await?;
+        shell.stdin.flush().await?;
+
+        let mut output = Vec::new();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if shell.stdout.read_until(b'\n', &mut line).await? == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            let text = String::from_utf8_lossy(&line);
+            if let Some(status) = text.strip_prefix(&shell.sentinel) {
+                let status = status.trim();
+                // Remove trailing newlines, including the one printed before the sentinel.
+                while output.last() == Some(&b'\n') {
+                    output.pop();
+                }
+                let mut output = self.truncate(String::from_utf8_lossy(&output).into_owned());
+                if status != "0" {
+                    if !output.is_empty() {
+                        output.push('\n');
+                    }
+                    output.push_str(&format!("[exit code {status}]"));
+                }
+                return Ok(output);
+            }
+            output.extend_from_slice(&line);
+        }
+    }
+
+    fn truncate(&self, mut output: String) -> String {
+        if output.len() > self.max_output {
+            let mut end = self.max_output;
+            while !output.is_char_boundary(end) {
+                end -= 1;
+            }
+            let omitted = output.len() - end;
+            output.truncate(end);
+            output.push_str(&format!("\n[output truncated, {omitted} bytes omitted]"));
+        }
+        output
+    }
+}
+
+/// Quotes a string for use as a single shell word.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+impl ToolHandler for BashExecutor {
+    type Input = Bash;
+
+    fn name(&self) -> &str {
+        BASH_NAME
+    }
+
+    fn definition(&self) -> crate::Result<Tool> {
+        Ok(Tool::Bash {
+            name: BASH_NAME.into(),
+            typ: self.typ.clone(),
+            cache_control: None,
+        })
+    }
+
+    async fn call(&self, input: Bash) -> Result<ToolResultContent, ToolError> {
+        Ok(self.execute(input).await?.into())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bash() {
+        let dir = tempfile::tempdir().unwrap();
+        let bash = BashExecutor::new(dir.path())
+            .unwrap()
+            .with_timeout(Duration::from_millis(500))
+            .with_max_output(20)
+            .with_env("GREETING", "hi");
+        let run = |command: &str| {
+            bash.execute(Bash::Command {
+                command: command.into(),
+            })
+        };
+
+        assert_eq!(run("echo $GREETING").await.unwrap(), "hi");
+        assert_eq!(
+            run("echo ${ANTHROPIC_API_KEY:-unset}").await.unwrap(),
+            "unset"
+        );
+        assert_eq!(
+            run("echo $HOME").await.unwrap(),
+            bash.root.to_string_lossy()
+        );
+
+        // State persists between commands.
+        run("mkdir sub && cd sub && export COUNT=1").await.unwrap();
+        assert_eq!(run("basename $PWD; echo $COUNT").await.unwrap(), "sub\n1");
+        assert_eq!(run("(exit 3)").await.unwrap(), "[exit code 3]");
+        assert_eq!(
+            run("echo oops >&2; false").await.unwrap(),
+            "oops\n[exit code 1]"
+        );
+        assert_eq!(run("printf no-newline").await.unwrap(), "no-newline");
+        assert!(run("cd /").await.unwrap().starts_with("[moved back to "));
+        assert_eq!(run("pwd").await.unwrap(), bash.root.to_string_lossy());
+
+        assert_eq!(
+            run("seq 1 100").await.unwrap(),
+            "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n[output truncated, 271 bytes omitted]"
+        );
+
+        let err = run("sleep 5").await.unwrap_err();
+        assert!(err.starts_with("Command timed out"));
+        assert_eq!(run("echo ${COUNT:-reset}").await.unwrap(), "reset");
+
+        run("export COUNT=2").await.unwrap();
+        let keep = serde_json::from_value(json!({"restart": false})).unwrap();
+        assert!(bash.execute(keep).await.is_err());
+        assert_eq!(run("echo ${COUNT:-reset}").await.unwrap(), "2");
+        let restart = serde_json::from_value(json!({"restart": true})).unwrap();
+        assert_eq!(bash.execute(restart).await.unwrap(), "Shell restarted.");
+        assert_eq!(run("echo ${COUNT:-reset}").await.unwrap(), "reset");
+    }
+}
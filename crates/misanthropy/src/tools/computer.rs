@@ -0,0 +1,308 @@
+//! An executor for the built-in computer use tool, backed by a pluggable display.
+use std::{future::Future, time::Duration};
+
+use super::{ComputerAction, Coordinate, Region};
+use crate::{
+    Content, Image, Tool, ToolError, ToolHandler, ToolResultContent, COMPUTER_37, COMPUTER_NAME,
+};
+
+/// A display that computer use actions are performed on, such as a virtual framebuffer or a
+/// fake display in tests.
+pub trait ComputerBackend: Send + Sync + 'static {
+    /// Captures the whole display.
+    fn screenshot(&self) -> impl Future<Output = Result<Image, ToolError>> + Send;
+
+    /// Returns the position of the mouse cursor.
+    fn cursor_position(&self) -> impl Future<Output = Result<Coordinate, ToolError>> + Send;
+
+    /// Performs a mouse or keyboard action. This is called for every action except
+    /// `Screenshot`, `CursorPosition`, `Wait` and `Zoom`. Coordinates have already been checked
+    /// against the display size.
+    fn perform(
+        &self,
+        action: &ComputerAction,
+    ) -> impl Future<Output = Result<(), ToolError>> + Send;
+
+    /// Captures a region of the display at full resolution. Not supported by default.
+    fn zoom(&self, _region: Region) -> impl Future<Output = Result<Image, ToolError>> + Send {
+        async { Err("The zoom action is not supported".into()) }
+    }
+}
+
+/// Runs computer use actions on a `ComputerBackend`, returning a screenshot after each action
+/// so the model can see its effect.
+///
+/// ```no_run
+/// # use misanthropy::{tools::{ComputerBackend, ComputerExecutor}, ToolRegistry, COMPUTER_37};
+/// # fn example(display: impl ComputerBackend) {
+/// let computer = ComputerExecutor::new(display, 1024, 768).with_type(COMPUTER_37);
+/// let tools = ToolRegistry::new().with_handler(computer);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ComputerExecutor<B> {
+    backend: B,
+    typ: String,
+    width: u32,
+    height: u32,
+    display_number: Option<u32>,
+    enable_zoom: bool,
+    screenshot_delay: Duration,
+}
+
+impl<B: ComputerBackend> ComputerExecutor<B> {
+    /// Creates an executor for a display of `width` by `height` pixels, using the `COMPUTER_37`
+    /// tool type. Screenshots are taken one second after each action.
+    pub fn new(backend: B, width: u32, height: u32) -> Self {
+        Self {
+            backend,
+            typ: COMPUTER_37.into(),
+            width,
+            height,
+            display_number: None,
+            enable_zoom: false,
+            screenshot_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Sets the computer use tool type. This must match the model, see `COMPUTER_35`,
+    /// `COMPUTER_37` and `COMPUTER_45`.
+    pub fn with_type(mut self, typ: impl Into<String>) -> Self {
+        self.typ = typ.into();
+        self
+    }
+
+    /// Sets the X11 display number sent in the tool definition.
+    pub fn with_display_number(mut self, display_number: u32) -> Self {
+        self.display_number = Some(display_number);
+        self
+    }
+
+    /// Enables the zoom action. Requires `COMPUTER_45` and a backend that implements
+    /// `ComputerBackend::zoom`.
+    pub fn with_zoom(mut self, enable_zoom: bool) -> Self {
+        self.enable_zoom = enable_zoom;
+        self
+    }
+
+    /// Sets how long to wait after an action before taking the screenshot, so the display
+    /// can settle.
+    pub fn with_screenshot_delay(mut self, delay: Duration) -> Self {
+        self.screenshot_delay = delay;
+        self
+    }
+
+    /// The backend actions are performed on.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Performs an action, returning a screenshot or text for the model.
+    pub async fn execute(&self, action: ComputerAction) -> Result<ToolResultContent, ToolError> {
+        for Coordinate { x, y } in action.coordinates() {
+            if x >= self.width || y >= self.height {
+                return Err(format!(
+                    "Coordinate ({x}, {y}) is outside the {}x{} display",
+                    self.width, self.height
+                )
+                .into());
+            }
+        }
+
+        let image = match action {
+            ComputerAction::Screenshot => self.backend.screenshot().await?,
+            ComputerAction::CursorPosition => {
+                let Coordinate { x, y } = self.backend.cursor_position().await?;
+                return Ok(format!("X={x},Y={y}").into());
+            }
+            ComputerAction::Zoom { region } => {
+                if region.top_left.x >= region.bottom_right.x
+                    || region.top_left.y >= region.bottom_right.y
+                {
+                    return Err("The zoom region is empty".into());
+                }
+                self.backend.zoom(region).await?
+            }
+            ComputerAction::Wait { duration } => {
+                tokio::time::sleep(seconds(duration)?).await;
+                self.backend.screenshot().await?
+            }
+            action => {
+                if let ComputerAction::HoldKey { duration, .. } = action {
+                    seconds(duration)?;
+                }
+                self.backend.perform(&action).await?;
+                tokio::time::sleep(self.screenshot_delay).await;
+                self.backend.screenshot().await?
+            }
+        };
+        Ok(vec![Content::Image(image)].into())
+    }
+}
+
+/// Converts a duration in seconds from the model, which must be between 0 and 100 seconds.
+fn seconds(duration: f64) -> Result<Duration, ToolError> {
+    if (0.0..=100.0).contains(&duration) {
+        Ok(Duration::from_secs_f64(duration))
+    } else {
+        Err(format!("Invalid duration {duration}, expected 0 to 100 seconds").into())
+    }
+}
+
+impl<B: ComputerBackend> ToolHandler for ComputerExecutor<B> {
+    type Input = ComputerAction;
+
+    fn name(&self) -> &str {
+        COMPUTER_NAME
+    }
+
+    fn definition(&self) -> crate::Result<Tool> {
+        Ok(Tool::Computer {
+            name: COMPUTER_NAME.into(),
+            typ: self.typ.clone(),
+            display_width_px: self.width,
+            display_height_px: self.height,
+            display_number: self.display_number,
+            enable_zoom: self.enable_zoom.then_some(true),
+            cache_control: None,
+        })
+    }
+
+    async fn call(&self, input: ComputerAction) -> Result<ToolResultContent, ToolError> {
+        self.execute(input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{tools::ScrollDirection, Source, ToolRegistry, ToolUse, COMPUTER_45};
+
+    #[derive(Default)]
+    struct FakeDisplay {
+        actions: Mutex<Vec<ComputerAction>>,
+    }
+
+    impl ComputerBackend for FakeDisplay {
+        async fn screenshot(&self) -> Result<Image, ToolError> {
+            let count = self.actions.lock().unwrap().len();
+            Ok(Image::base64("image/png", format!("screen {count}")))
+        }
+
+        async fn cursor_position(&self) -> Result<Coordinate, ToolError> {
+            Ok(Coordinate::new(10, 20))
+        }
+
+        async fn perform(&self, action: &ComputerAction) -> Result<(), ToolError> {
+            self.actions.lock().unwrap().push(action.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_computer_action_serialization() {
+        let action: ComputerAction = serde_json::from_value(json!({
+            "action": "scroll",
+            "coordinate": [100, 200],
+            "scroll_direction": "down",
+            "scroll_amount": 3
+        }))
+        .unwrap();
+        assert_eq!(
+            action,
+            ComputerAction::Scroll {
+                coordinate: Some(Coordinate::new(100, 200)),
+                scroll_direction: ScrollDirection::Down,
+                scroll_amount: 3,
+                text: None,
+            }
+        );
+
+        let zoom = ComputerAction::Zoom {
+            region: [0, 0, 400, 300].into(),
+        };
+        assert_eq!(
+            serde_json::to_value(&zoom).unwrap(),
+            json!({"action": "zoom", "region": [0, 0, 400, 300]})
+        );
+        assert_eq!(
+            serde_json::to_value(ComputerAction::LeftClick {
+                coordinate: None,
+                text: Some("shift".into()),
+            })
+            .unwrap(),
+            json!({"action": "left_click", "text": "shift"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_computer_executor() {
+        let computer = ComputerExecutor::new(FakeDisplay::default(), 1024, 768)
+            .with_type(COMPUTER_45)
+            .with_display_number(1)
+            .with_zoom(true)
+            .with_screenshot_delay(Duration::ZERO);
+        let tools = ToolRegistry::new().with_handler(computer);
+        let definition = serde_json::to_value(tools.tools().unwrap()).unwrap();
+        assert_eq!(
+            definition,
+            json!([{
+                "name": "computer",
+                "type": "computer_20251124",
+                "display_width_px": 1024,
+                "display_height_px": 768,
+                "display_number": 1,
+                "enable_zoom": true
+            }])
+        );
+
+        let call = |input| async {
+            let tool_use = ToolUse::new("toolu_01".into(), "computer".into(), input);
+            let result = tools.tool_result(&tool_use).await;
+            (result.content, result.is_error)
+        };
+
+        let (content, is_error) = call(json!({
+            "action": "left_click_drag",
+            "start_coordinate": [10, 10],
+            "coordinate": [500, 300]
+        }))
+        .await;
+        assert!(!is_error);
+        match &content {
+            ToolResultContent::Blocks(blocks) => match &blocks[..] {
+                [Content::Image(Image {
+                    source: Source::Base64 { media_type, data },
+                    ..
+                })] => {
+                    assert_eq!(media_type, "image/png");
+                    assert_eq!(data, "c2NyZWVuIDE=");
+                }
+                _ => panic!("Expected a screenshot"),
+            },
+            _ => panic!("Expected ToolResultContent::Blocks"),
+        }
+
+        let (content, _) = call(json!({"action": "cursor_position"})).await;
+        assert_eq!(content.to_string(), "X=10,Y=20");
+
+        let (content, is_error) =
+            call(json!({"action": "mouse_move", "coordinate": [1024, 0]})).await;
+        assert!(is_error);
+        assert_eq!(
+            content.to_string(),
+            "Coordinate (1024, 0) is outside the 1024x768 display"
+        );
+
+        let (content, is_error) = call(json!({"action": "zoom", "region": [0, 0, 10, 10]})).await;
+        assert!(is_error);
+        assert_eq!(content.to_string(), "The zoom action is not supported");
+
+        let (_, is_error) = call(json!({"action": "wait", "duration": -1})).await;
+        assert!(is_error);
+    }
+}
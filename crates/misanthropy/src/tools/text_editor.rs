@@ -0,0 +1,388 @@
+//! An executor for the built-in text editor tool.
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+use super::TextEditor;
+use crate::{
+    Tool, ToolError, ToolHandler, ToolResultContent, TEXT_EDITOR_4, TEXT_EDITOR_NAME_3,
+    TEXT_EDITOR_NAME_4,
+};
+
+/// Applies text editor commands to files inside a sandbox directory.
+///
+/// Paths from the model are resolved inside the sandbox root. Absolute paths are treated as
+/// relative to the root, and paths that would escape it, through `..` or symlinks, are rejected.
+/// Every edit is recorded so it can be reverted with `undo_edit`.
+///
+/// The executor is a `ToolHandler`, so it can be added to a `ToolRegistry` or `Agent`:
+///
+/// ```no_run
+/// # use misanthropy::{tools::TextEditorExecutor, ToolRegistry};
+/// let editor = TextEditorExecutor::new("/tmp/workspace")?;
+/// let tools = ToolRegistry::new().with_handler(editor);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct TextEditorExecutor {
+    root: PathBuf,
+    typ: String,
+    /// Previous contents of edited files, most recent last. `None` means the file didn't exist.
+    history: Mutex<HashMap<PathBuf, Vec<Option<String>>>>,
+}
+
+impl TextEditorExecutor {
+    /// Creates an executor for files in `root`, using the `TEXT_EDITOR_4` tool type.
+    pub fn new(root: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self {
+            root: root.as_ref().canonicalize()?,
+            typ: TEXT_EDITOR_4.into(),
+            history: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Sets the text editor tool type. This must match the model, see `TEXT_EDITOR_4`,
+    /// `TEXT_EDITOR_37` and `TEXT_EDITOR_35`.
+    pub fn with_type(mut self, typ: impl Into<String>) -> Self {
+        self.typ = typ.into();
+        self
+    }
+
+    /// The sandbox directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Runs a command, returning the output for the model or an error message.
+    pub fn execute(&self, command: TextEditor) -> Result<String, String> {
+        match command {
+            TextEditor::View { path, view_range } => self.view(&path, view_range),
+            TextEditor::StrReplace {
+                path,
+                old_str,
+                new_str,
+            } => {
+                let file = self.resolve(&path)?;
+                let content = read(&file, &path)?;
+                match content.matches(&old_str).count() {
+                    0 => Err(format!(
+                        "No match found for replacement text in {path}. Please check your text \
+                         and try again."
+                    )),
+                    1 => {
+                        self.write(&file, &path, content.replacen(&old_str, &new_str, 1))?;
+                        Ok("Successfully replaced text at exactly one location.".into())
+                    }
+                    n => Err(format!(
+                        "Found {n} matches for replacement text in {path}. Please provide more \
+                         context to make a unique match."
+                    )),
+                }
+            }
+            TextEditor::Create { path, file_text } => {
+                let file = self.resolve(&path)?;
+                if file.exists() {
+                    return Err(format!("File already exists at {path}."));
+                }
+                if let Some(parent) = file.parent() {
+                    fs::create_dir_all(parent).map_err(|e| format!("{path}: {e}"))?;
+                }
+                self.write(&file, &path, file_text)?;
+                Ok(format!("Successfully created {path}."))
+            }
+            TextEditor::Insert {
+                path,
+                insert_line,
+                new_str,
+            } => {
+                let file = self.resolve(&path)?;
+                let content = read(&file, &path)?;
+                let mut lines: Vec<&str> = content.lines().collect();
+                if insert_line > lines.len() {
+                    return Err(format!(
+                        "Invalid insert_line {insert_line}. It should be between 0 and {}.",
+                        lines.len()
+                    ));
+                }
+                lines.splice(insert_line..insert_line, new_str.lines());
+                let mut updated = lines.join("\n");
+                if content.ends_with('\n') || content.is_empty() {
+                    updated.push('\n');
+                }
+                self.write(&file, &path, updated)?;
+                Ok(format!(
+                    "Successfully inserted text after line {insert_line}."
+                ))
+            }
+            TextEditor::UndoEdit { path } => {
+                let file = self.resolve(&path)?;
+                let previous = self
+                    .history
+                    .lock()
+                    .unwrap()
+                    .get_mut(&file)
+                    .and_then(Vec::pop)
+                    .ok_or_else(|| format!("No edit history found for {path}."))?;
+                match previous {
+                    Some(content) => fs::write(&file, content),
+                    None => fs::remove_file(&file),
+                }
+                .map_err(|e| format!("{path}: {e}"))?;
+                Ok(format!("Successfully reverted the last edit to {path}."))
+            }
+        }
+    }
+
+    /// Shows a file with line numbers, or lists a directory.
+    fn view(&self, path: &str, view_range: Option<[i32; 2]>) -> Result<String, String> {
+        let file = self.resolve(path)?;
+        if file.is_dir() {
+            if view_range.is_some() {
+                return Err("view_range is not allowed when viewing a directory.".into());
+            }
+            let mut entries = Vec::new();
+            list_dir(&file, &self.root, 2, &mut entries).map_err(|e| format!("{path}: {e}"))?;
+            entries.sort();
+            return Ok(entries.join("\n"));
+        }
+
+        let content = read(&file, path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        let (start, end) = match view_range {
+            None => (1, lines.len()),
+            Some([start, end]) => {
+                let end = if end == -1 { lines.len() as i32 } else { end };
+                if start < 1 || start > end || end as usize > lines.len() {
+                    return Err(format!(
+                        "Invalid view_range [{start}, {end}]. The file has {} lines.",
+                        lines.len()
+                    ));
+                }
+                (start as usize, end as usize)
+            }
+        };
+        Ok(lines
+            .iter()
+            .enumerate()
+            .take(end)
+            .skip(start.saturating_sub(1))
+            .map(|(i, line)| format!("{:6}\t{line}", i + 1))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Resolves a path from the model to a path inside the sandbox.
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let escape = || format!("{path} is outside the allowed directory.");
+        let requested = Path::new(path);
+        let relative = requested.strip_prefix(&self.root).unwrap_or(requested);
+        let mut resolved = self.root.clone();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::ParentDir => {
+                    if resolved == self.root {
+                        return Err(escape());
+                    }
+                    resolved.pop();
+                }
+                Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+            }
+        }
+
+        // Symlinks could still point outside the sandbox, so check where the existing part of the
+        // path really is. A broken symlink counts as existing, since writing through it would
+        // create its target wherever that is.
+        let mut existing = resolved.as_path();
+        while fs::symlink_metadata(existing).is_err() {
+            existing = existing.parent().ok_or_else(escape)?;
+        }
+        if !existing.exists() {
+            return Err(format!("{path} is a broken symlink."));
+        }
+        let canonical = existing
+            .canonicalize()
+            .map_err(|e| format!("{path}: {e}"))?;
+        if !canonical.starts_with(&self.root) {
+            return Err(escape());
+        }
+        Ok(resolved)
+    }
+
+    /// Writes a file, recording its previous content for `undo_edit`.
+    fn write(&self, file: &Path, path: &str, content: String) -> Result<(), String> {
+        let previous = fs::read_to_string(file).ok();
+        fs::write(file, content).map_err(|e| format!("{path}: {e}"))?;
+        self.history
+            .lock()
+            .unwrap()
+            .entry(file.to_path_buf())
+            .or_default()
+            .push(previous);
+        Ok(())
+    }
+}
+
+fn read(file: &Path, path: &str) -> Result<String, String> {
+    fs::read_to_string(file).map_err(|e| format!("{path}: {e}"))
+}
+
+/// Lists the non-hidden files and directories in `dir`, up to `depth` levels deep.
+fn list_dir(
+    dir: &Path,
+    root: &Path,
+    depth: usize,
+    entries: &mut Vec<String>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        let display = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        if path.is_dir() {
+            entries.push(format!("{display}/"));
+            if depth > 1 {
+                list_dir(&path, root, depth - 1, entries)?;
+            }
+        } else {
+            entries.push(display);
+        }
+    }
+    Ok(())
+}
+
+impl ToolHandler for TextEditorExecutor {
+    type Input = TextEditor;
+
+    fn name(&self) -> &str {
+        if self.typ == TEXT_EDITOR_4 {
+            TEXT_EDITOR_NAME_4
+        } else {
+            TEXT_EDITOR_NAME_3
+        }
+    }
+
+    fn definition(&self) -> crate::Result<Tool> {
+        Ok(Tool::TextEditor {
+            name: ToolHandler::name(self).into(),
+            typ: self.typ.clone(),
+            cache_control: None,
+        })
+    }
+
+    async fn call(&self, input: TextEditor) -> Result<ToolResultContent, ToolError> {
+        Ok(self.execute(input)?.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Content, ToolRegistry, ToolUse};
+
+    #[test]
+    fn test_text_editor() {
+        let dir = tempfile::tempdir().unwrap();
+        let editor = TextEditorExecutor::new(dir.path()).unwrap();
+        let run = |command| editor.execute(serde_json::from_value(command).unwrap());
+
+        run(json!({"command": "create", "path": "src/main.rs", "file_text": "fn main() {\n}\n"}))
+            .unwrap();
+        assert!(run(json!({"command": "create", "path": "src/main.rs", "file_text": ""})).is_err());
+        run(json!({"command": "insert", "path": "/src/main.rs", "insert_line": 1, "new_str": "    run();"}))
+            .unwrap();
+        assert_eq!(
+            run(json!({"command": "view", "path": "src/main.rs"})).unwrap(),
+            "     1\tfn main() {\n     2\t    run();\n     3\t}"
+        );
+        assert_eq!(
+            run(json!({"command": "view", "path": "src/main.rs", "view_range": [2, -1]})).unwrap(),
+            "     2\t    run();\n     3\t}"
+        );
+        assert_eq!(
+            run(json!({"command": "view", "path": "."})).unwrap(),
+            "src/\nsrc/main.rs"
+        );
+
+        let replace = |old: &str| {
+            run(
+                json!({"command": "str_replace", "path": "src/main.rs", "old_str": old, "new_str": "start();"}),
+            )
+        };
+        assert!(replace("stop();")
+            .unwrap_err()
+            .starts_with("No match found"));
+        run(json!({"command": "insert", "path": "src/main.rs", "insert_line": 2, "new_str": "    run();"}))
+            .unwrap();
+        assert!(replace("run();")
+            .unwrap_err()
+            .starts_with("Found 2 matches"));
+        run(json!({"command": "undo_edit", "path": "src/main.rs"})).unwrap();
+        replace("run();").unwrap();
+        let file = dir.path().join("src/main.rs");
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "fn main() {\n    start();\n}\n"
+        );
+
+        // Undoing the creation removes the file.
+        for _ in 0..3 {
+            run(json!({"command": "undo_edit", "path": "src/main.rs"})).unwrap();
+        }
+        assert!(!file.exists());
+        assert!(run(json!({"command": "undo_edit", "path": "src/main.rs"})).is_err());
+
+        for path in ["../outside.txt", "src/../../outside.txt"] {
+            let err = run(json!({"command": "create", "path": path, "file_text": "x"}));
+            assert!(err.unwrap_err().contains("outside the allowed directory"));
+        }
+        #[cfg(unix)]
+        {
+            let outside = tempfile::tempdir().unwrap();
+            std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+            let err = run(json!({"command": "create", "path": "link/x.txt", "file_text": "x"}));
+            assert!(err.unwrap_err().contains("outside the allowed directory"));
+
+            let target = outside.path().join("new.txt");
+            std::os::unix::fs::symlink(&target, dir.path().join("file_link")).unwrap();
+            let err = run(json!({"command": "create", "path": "file_link", "file_text": "x"}));
+            assert_eq!(err.unwrap_err(), "file_link is a broken symlink.");
+            assert!(!target.exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_text_editor_tool_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry =
+            ToolRegistry::new().with_handler(TextEditorExecutor::new(dir.path()).unwrap());
+        assert!(matches!(
+            &registry.tools().unwrap()[..],
+            [Tool::TextEditor { name, typ, .. }] if name == TEXT_EDITOR_NAME_4 && typ == TEXT_EDITOR_4
+        ));
+
+        let tool_use = ToolUse::new(
+            "toolu_01".into(),
+            TEXT_EDITOR_NAME_4.into(),
+            json!({"command": "view", "path": "missing.txt"}),
+        );
+        let Content::ToolResult(result) = registry.call(&tool_use).await else {
+            panic!("Expected Content::ToolResult");
+        };
+        assert!(result.is_error);
+        assert!(result.content.to_string().starts_with("missing.txt:"));
+    }
+}
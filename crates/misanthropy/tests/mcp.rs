@@ -0,0 +1,175 @@
+//! Tests for the MCP client. This test binary has no harness: when `FAKE_MCP_SERVER` is set,
+//! it runs as the fake MCP server the tests connect to, misbehaving as `FAKE_MCP_SERVER` says.
+use std::time::Duration;
+
+use misanthropy::{
+    Content, Error, McpClient, MessagesResponse, Source, Tool, ToolRegistry, ToolResultContent,
+    ToolUse,
+};
+use serde_json::json;
+use tokio::process::Command;
+
+#[path = "support/fake_mcp_server.rs"]
+mod fake_mcp_server;
+
+const SERVER_MODE: &str = "FAKE_MCP_SERVER";
+
+fn main() {
+    if let Ok(fault) = std::env::var(SERVER_MODE) {
+        fake_mcp_server::run(Some(fault.as_str()).filter(|f| !f.is_empty())).unwrap();
+        return;
+    }
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    print!("test test_mcp_client ... ");
+    runtime.block_on(test_mcp_client());
+    println!("ok");
+    print!("test test_mcp_tool_handlers ... ");
+    runtime.block_on(test_mcp_tool_handlers());
+    println!("ok");
+    print!("test test_mcp_timeout ... ");
+    runtime.block_on(test_mcp_timeout());
+    println!("ok");
+    print!("test test_mcp_repeated_cursor ... ");
+    runtime.block_on(test_mcp_repeated_cursor());
+    println!("ok");
+}
+
+/// A command that runs the fake server, with a `fault` as described in `fake_mcp_server::run`.
+fn server_command(fault: &str) -> Command {
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command.env(SERVER_MODE, fault);
+    command
+}
+
+async fn fake_server() -> McpClient {
+    McpClient::from_command(server_command("")).await.unwrap()
+}
+
+fn tool_use(id: &str, name: &str, input: serde_json::Value) -> ToolUse {
+    ToolUse::new(id.into(), name.into(), input)
+}
+
+async fn test_mcp_client() {
+    let client = fake_server().await;
+    assert_eq!(client.server_name(), "fake");
+
+    let tools = client.tools();
+    let names: Vec<_> = tools.iter().map(Tool::name).collect();
+    assert_eq!(names, ["echo", "screenshot", "fail"]);
+    match &tools[0] {
+        Tool::Custom {
+            description,
+            input_schema,
+            ..
+        } => {
+            assert_eq!(description, "Echo the text back.");
+            assert_eq!(input_schema.get("required"), Some(&json!(["text"])));
+        }
+        _ => panic!("Expected Tool::Custom"),
+    }
+
+    let result = client
+        .call(&tool_use("toolu_01", "echo", json!({"text": "hello"})))
+        .await;
+    assert_eq!(result.tool_use_id, "toolu_01");
+    assert_eq!(result.content.to_string(), "hello");
+    assert!(!result.is_error);
+
+    let result = client
+        .call(&tool_use("toolu_02", "screenshot", json!({})))
+        .await;
+    match &result.content {
+        ToolResultContent::Blocks(blocks) => match &blocks[..] {
+            [Content::Text(text), Content::Image(image)] => {
+                assert_eq!(text.text, "The screen:");
+                assert!(matches!(
+                    &image.source,
+                    Source::Base64 { media_type, data }
+                        if media_type == "image/png" && data == "iVBORw0KGgo="
+                ));
+            }
+            _ => panic!("Expected text and an image"),
+        },
+        _ => panic!("Expected ToolResultContent::Blocks"),
+    }
+
+    let result = client.call(&tool_use("toolu_03", "fail", json!({}))).await;
+    assert!(result.is_error);
+    assert_eq!(result.content.to_string(), "Something went wrong");
+
+    let result = client
+        .call(&tool_use("toolu_04", "missing", json!({})))
+        .await;
+    assert!(result.is_error);
+    assert_eq!(
+        result.content.to_string(),
+        "MCP error: tools/call failed: Unknown tool: missing"
+    );
+}
+
+async fn test_mcp_tool_handlers() {
+    let client = fake_server().await;
+    let mut registry = ToolRegistry::new();
+    for handler in client.handlers() {
+        registry.register(handler);
+    }
+    assert_eq!(registry.tools().unwrap().len(), 3);
+
+    let response = MessagesResponse {
+        content: vec![
+            Content::ToolUse(tool_use("toolu_01", "echo", json!({"text": "one"}))),
+            Content::ToolUse(tool_use("toolu_02", "echo", json!({"text": "two"}))),
+            Content::ToolUse(tool_use("toolu_03", "fail", json!({}))),
+        ],
+        ..Default::default()
+    };
+    let results: Vec<_> = registry
+        .run(&response)
+        .await
+        .into_iter()
+        .map(|result| match result {
+            Content::ToolResult(result) => (result.content.to_string(), result.is_error),
+            _ => panic!("Expected Content::ToolResult"),
+        })
+        .collect();
+    assert_eq!(
+        results,
+        [
+            ("one".to_string(), false),
+            ("two".to_string(), false),
+            ("Something went wrong".to_string(), true)
+        ]
+    );
+}
+
+async fn test_mcp_timeout() {
+    let timeout = Duration::from_millis(200);
+    let err = McpClient::from_command_with_timeout(server_command("initialize"), timeout)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::McpError(message)
+        if message == "initialize timed out after 0.2 seconds"));
+
+    // A hung tool call fails, and releases the connection for the next call.
+    let client = McpClient::from_command_with_timeout(server_command("tools/call"), timeout)
+        .await
+        .unwrap();
+    for id in ["toolu_01", "toolu_02"] {
+        let result = client
+            .call(&tool_use(id, "echo", json!({"text": "hello"})))
+            .await;
+        assert!(result.is_error);
+        assert_eq!(
+            result.content.to_string(),
+            "MCP error: tools/call timed out after 0.2 seconds"
+        );
+    }
+}
+
+async fn test_mcp_repeated_cursor() {
+    let err = McpClient::from_command(server_command("repeat_cursor"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::McpError(message)
+        if message == "tools/list returned a repeated or empty page with cursor 2"));
+}
@@ -0,0 +1,105 @@
+//! A minimal MCP server over stdio, used by the MCP client tests.
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+/// Serves MCP requests on stdin and stdout until stdin is closed. `fault` makes the server
+/// misbehave: `repeat_cursor` returns the same tools/list cursor forever, and any other value
+/// is a method whose requests are never answered, to simulate a hung server.
+pub fn run(fault: Option<&str>) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let mut send = |message: Value| -> io::Result<()> {
+        writeln!(stdout, "{message}")?;
+        stdout.flush()
+    };
+
+    let mut lines = io::stdin().lock().lines();
+    while let Some(line) = lines.next() {
+        let request: Value = serde_json::from_str(&line?)?;
+        let Some(id) = request.get("id").cloned() else {
+            // A notification, such as notifications/initialized.
+            continue;
+        };
+        if request["method"].as_str() == fault {
+            continue;
+        }
+        let params = &request["params"];
+        let result = match request["method"].as_str().unwrap_or_default() {
+            "initialize" => json!({
+                "protocolVersion": params["protocolVersion"],
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "fake", "version": "1.0.0"}
+            }),
+            // Tools are listed in two pages to exercise pagination.
+            "tools/list" if params.get("cursor").is_none() => json!({
+                "tools": [{
+                    "name": "echo",
+                    "description": "Echo the text back.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {"text": {"type": "string"}},
+                        "required": ["text"]
+                    }
+                }],
+                "nextCursor": "2"
+            }),
+            "tools/list" if fault == Some("repeat_cursor") => json!({
+                "tools": [{"name": "screenshot", "inputSchema": {"type": "object"}}],
+                "nextCursor": "2"
+            }),
+            "tools/list" => json!({
+                "tools": [
+                    {"name": "screenshot", "inputSchema": {"type": "object"}},
+                    {"name": "fail", "inputSchema": {"type": "object"}}
+                ]
+            }),
+            "tools/call" => {
+                // Send a log notification and a ping first, which the client must handle
+                // while waiting for the result.
+                send(json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/message",
+                    "params": {"level": "info", "data": "calling a tool"}
+                }))?;
+                send(json!({"jsonrpc": "2.0", "id": "ping-1", "method": "ping"}))?;
+                let pong = lines.next().expect("a reply to the ping")?;
+                let pong: Value = serde_json::from_str(&pong)?;
+                assert_eq!(pong["id"], "ping-1");
+
+                match params["name"].as_str().unwrap_or_default() {
+                    "echo" => json!({
+                        "content": [{"type": "text", "text": params["arguments"]["text"]}]
+                    }),
+                    "screenshot" => json!({
+                        "content": [
+                            {"type": "text", "text": "The screen:"},
+                            {"type": "image", "data": "iVBORw0KGgo=", "mimeType": "image/png"}
+                        ]
+                    }),
+                    "fail" => json!({
+                        "content": [{"type": "text", "text": "Something went wrong"}],
+                        "isError": true
+                    }),
+                    name => {
+                        send(json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": {"code": -32602, "message": format!("Unknown tool: {name}")}
+                        }))?;
+                        continue;
+                    }
+                }
+            }
+            method => {
+                send(json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": format!("Method not found: {method}")}
+                }))?;
+                continue;
+            }
+        };
+        send(json!({"jsonrpc": "2.0", "id": id, "result": result}))?;
+    }
+    Ok(())
+}